@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::process::exit;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::info;
+use sfml::graphics::{RenderTarget, RenderWindow};
+use sfml::window::{mouse, Event, Key};
+
+use crate::chart::ChartSet;
+use crate::config::DisplayConfig;
+use crate::gps::NavState;
+use crate::nmea::Sentence;
+use crate::render::{render_chart_set, render_own_ship, render_readout, render_scale_bar};
+use crate::transform::Projection;
+use crate::viewport::Viewport;
+
+// how often data sources like the gps are checked for anything new
+const DATA_UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The interactive window: events are queued up and handled at the start of each frame, data sources
+/// are checked on a timer and frames are capped to the configured rate. With `redraw_on_demand` a frame
+/// is only drawn when something changed, which saves a lot of battery on a laptop at the nav station.
+pub struct App {
+    window: RenderWindow,
+    charts: ChartSet,
+    viewport: Viewport,
+    events: VecDeque<Event>,
+    gps: Option<Receiver<Sentence>>,
+    nav: NavState,
+    // pixel the mouse is over, for the depth readout
+    cursor: Option<(i32, i32)>,
+    render_depth: bool,
+    frame_time: Duration,
+    redraw_on_demand: bool,
+    next_data_update: Instant,
+    // something changed since the last frame was drawn
+    dirty: bool,
+}
+
+impl App {
+    pub fn new(window: RenderWindow, charts: ChartSet, gps: Option<Receiver<Sentence>>, display: &DisplayConfig) -> App {
+        let viewport = Viewport::new(&charts.depare_map, (window.size().x, window.size().y));
+        info!("center: {:?}", viewport.view.center());
+        App {
+            window,
+            charts,
+            viewport,
+            events: VecDeque::new(),
+            gps,
+            nav: NavState::default(),
+            cursor: None,
+            render_depth: false,
+            frame_time: Duration::from_secs_f64(1.0 / display.frame_rate as f64),
+            redraw_on_demand: display.redraw_on_demand,
+            next_data_update: Instant::now(),
+            dirty: true,
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            let frame_start = Instant::now();
+            self.queue_events();
+            while let Some(event) = self.events.pop_front() {
+                self.handle_event(event);
+            }
+            if frame_start >= self.next_data_update {
+                self.update_data();
+                self.next_data_update = frame_start + DATA_UPDATE_INTERVAL;
+            }
+            if self.viewport.animate() {
+                self.dirty = true;
+            }
+            if self.dirty || !self.redraw_on_demand {
+                self.draw();
+                self.dirty = false;
+            }
+            if let Some(rest) = self.frame_time.checked_sub(frame_start.elapsed()) {
+                thread::sleep(rest);
+            }
+        }
+    }
+
+    /// moves everything the window has into our queue, when there's nothing that could change
+    /// on its own we sleep in the window until the user does something
+    fn queue_events(&mut self) {
+        let idle = self.redraw_on_demand && !self.dirty && self.gps.is_none() && !self.viewport.is_animating();
+        if idle {
+            self.events.extend(self.window.wait_event());
+        }
+        while let Some(event) = self.window.poll_event() {
+            self.events.push_back(event);
+        }
+    }
+
+    fn quit(&mut self) -> ! {
+        self.window.close();
+        exit(0);
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        let viewport = &mut self.viewport;
+        match event {
+            Event::Closed => self.quit(),
+            Event::KeyPressed { code: Key::Escape, .. } => self.quit(),
+            Event::KeyPressed { code: Key::Q, .. } => self.quit(),
+            Event::KeyPressed { code: Key::Up, .. } => viewport.pan_fraction(0.0, -0.1),
+            Event::KeyPressed { code: Key::Down, .. } => viewport.pan_fraction(0.0, 0.1),
+            Event::KeyPressed { code: Key::Left, .. } => viewport.pan_fraction(-0.1, 0.0),
+            Event::KeyPressed { code: Key::Right, .. } => viewport.pan_fraction(0.1, 0.0),
+            Event::Resized { width, height } => viewport.resize(width, height),
+            Event::KeyPressed { code: Key::W, .. } => viewport.zoom_at_center(0.9),
+            Event::KeyPressed { code: Key::S, .. } => viewport.zoom_at_center(1.1),
+            Event::KeyPressed { code: Key::F, .. } => viewport.zoom_to_fit(&self.charts.depare_map),
+            Event::KeyPressed { code: Key::D, .. } => self.render_depth = !self.render_depth,
+            Event::MouseWheelScrolled { wheel: mouse::Wheel::VerticalWheel, delta, x, y } => viewport.scroll(delta, x, y),
+            Event::MouseButtonPressed { button: mouse::Button::Left, x, y } => viewport.start_drag(x, y),
+            Event::MouseButtonReleased { button: mouse::Button::Left, x, y } => {
+                let clicked = viewport.end_drag();
+                if clicked {
+                    let world = self.window.map_pixel_to_coords((x, y).into(), &viewport.view);
+                    let (lon, lat) = self.charts.projection.inverse((world.x as f64, world.y as f64));
+                    info!("clicked lon: {lon:.5} lat: {lat:.5}");
+                }
+            }
+            Event::MouseMoved { x, y } => {
+                viewport.drag_to(x, y);
+                self.cursor = Some((x, y));
+            }
+            Event::MouseLeft => self.cursor = None,
+            Event::TouchBegan { finger, x, y } => viewport.touch_began(finger, x, y),
+            Event::TouchMoved { finger, x, y } => viewport.touch_moved(finger, x, y),
+            Event::TouchEnded { finger, .. } => viewport.touch_ended(finger),
+            // focus changes and the like don't need a new frame
+            _ => return,
+        }
+        self.dirty = true;
+    }
+
+    /// pulls in whatever the data sources have for us
+    fn update_data(&mut self) {
+        let had_fix = self.nav.has_fix();
+        if let Some(gps) = &self.gps {
+            if self.nav.update_from(gps) {
+                self.dirty = true;
+            }
+        }
+        // the boat goes grey when the fix goes stale, even if nothing new arrives
+        if had_fix != self.nav.has_fix() {
+            self.dirty = true;
+        }
+    }
+
+    fn draw(&mut self) {
+        let window = &mut self.window;
+        // minimised, there's nothing to draw into
+        if window.size().x == 0 || window.size().y == 0 {
+            return;
+        }
+        let (view, zoom) = (&self.viewport.view, self.viewport.zoom);
+        render_chart_set(window, &self.charts, view, zoom, self.render_depth);
+        render_own_ship(window, &self.nav, &self.charts.projection, zoom);
+        render_scale_bar(window, &self.charts.projection, view, zoom, &self.charts.units, &self.charts.font);
+        let mut readout = self.nav.readout(&self.charts.units);
+        if let Some((x, y)) = self.cursor {
+            let world = window.map_pixel_to_coords((x, y).into(), view);
+            let (lon, lat) = self.charts.projection.inverse((world.x as f64, world.y as f64));
+            readout.extend(self.charts.cursor_readout(lat, lon));
+        }
+        render_readout(window, &readout, &self.charts.font);
+        window.display();
+    }
+}
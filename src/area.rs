@@ -0,0 +1,317 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+
+use gdal::Dataset;
+use gdal::vector::LayerAccess;
+use geo::{InteriorPoint, Polygon};
+use sfml::graphics::{Color, PrimitiveType, RenderStates, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+
+use crate::attributes::{Attributes, Restriction, RestrictedAreaCategory};
+use crate::convert::ConversionStats;
+use crate::geometry::{get_layers, project_geometry, LayerExtent};
+use crate::line::{tessellate_polyline, LineCap};
+use crate::render::{get_view_extent, visible, Symbology};
+use crate::transform::Projection;
+
+pub const AREA_FILL_NAMES: [&str; 3] = ["solid", "none", "hatch"];
+pub const AREA_SYMBOL_NAMES: [&str; 7] = ["anchorage", "no_anchoring", "no_entry", "no_fishing", "caution", "information", "restrictions"];
+
+// hatch lines are this many pixels wide
+const HATCH_WIDTH: f32 = 1.0;
+
+/// How the inside of an area is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaFill {
+    Solid,
+    // outline and symbol only, the water underneath shows through
+    None,
+    // parallel lines across the area
+    Hatch,
+}
+
+impl AreaFill {
+    pub fn from_name(name: &str) -> Option<AreaFill> {
+        match name.to_lowercase().as_str() {
+            "solid" => Some(AreaFill::Solid),
+            "none" => Some(AreaFill::None),
+            "hatch" => Some(AreaFill::Hatch),
+            _ => None,
+        }
+    }
+}
+
+/// The symbol drawn in the middle of an area, after the S-52 ones
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaSymbol {
+    // ACHARE51
+    Anchorage,
+    // ACHRES51
+    AnchoringProhibited,
+    // ENTRES51
+    EntryProhibited,
+    // FSHRES51
+    FishingProhibited,
+    // CTYARE51
+    Caution,
+    // INFARE51
+    Information,
+}
+
+impl AreaSymbol {
+    /// the symbol as strokes in pixels around the middle of the area, y down
+    pub fn strokes(&self) -> Vec<Vec<Vector2f>> {
+        let v = Vector2f::new;
+        let cross = vec![vec![v(-7.0, -7.0), v(7.0, 7.0)], vec![v(-7.0, 7.0), v(7.0, -7.0)]];
+        match self {
+            AreaSymbol::Anchorage => {
+                // the arms are the bottom of a circle around the middle
+                let arms = (30..=150).step_by(10).map(|x| (x as f32).to_radians()).map(|a| v(a.cos(), a.sin()) * 7.0).collect();
+                vec![ring(v(0.0, -7.0), 1.5), vec![v(0.0, -5.5), v(0.0, 7.0)], vec![v(-4.0, -3.5), v(4.0, -3.5)], arms, vec![v(-6.1, 3.5), v(-6.5, 1.0)], vec![v(6.1, 3.5), v(6.5, 1.0)]]
+            }
+            AreaSymbol::AnchoringProhibited => [AreaSymbol::Anchorage.strokes(), cross].concat(),
+            AreaSymbol::EntryProhibited => vec![ring(v(0.0, 0.0), 7.0), vec![v(-4.0, 0.0), v(4.0, 0.0)]],
+            AreaSymbol::FishingProhibited => {
+                let body = (0..=16).map(|x| x as f32 * PI / 8.0).map(|a| v(-1.0 + 5.0 * a.cos(), 2.5 * a.sin())).collect();
+                [vec![body, vec![v(4.0, 0.0), v(7.0, -3.0), v(7.0, 3.0), v(4.0, 0.0)]], cross].concat()
+            }
+            // a short stroke with round caps makes the dot
+            AreaSymbol::Caution => vec![vec![v(0.0, -6.0), v(0.0, 2.0)], vec![v(0.0, 5.0), v(0.0, 5.1)]],
+            AreaSymbol::Information => vec![vec![v(0.0, -6.0), v(0.0, -5.9)], vec![v(0.0, -2.0), v(0.0, 6.0)]],
+        }
+    }
+}
+
+fn ring(center: Vector2f, radius: f32) -> Vec<Vector2f> {
+    (0..=16).map(|i| i as f32 * PI / 8.0).map(|a| center + Vector2f::new(a.cos(), a.sin()) * radius).collect()
+}
+
+/// Which symbol an area layer gets, one for the whole layer or worked out per feature
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaSymbolRule {
+    Fixed(AreaSymbol),
+    // from RESTRN and CATREA, like S-52's RESARE02
+    Restrictions,
+}
+
+impl AreaSymbolRule {
+    pub fn from_name(name: &str) -> Option<AreaSymbolRule> {
+        match name.to_lowercase().as_str() {
+            "anchorage" => Some(AreaSymbolRule::Fixed(AreaSymbol::Anchorage)),
+            "no_anchoring" => Some(AreaSymbolRule::Fixed(AreaSymbol::AnchoringProhibited)),
+            "no_entry" => Some(AreaSymbolRule::Fixed(AreaSymbol::EntryProhibited)),
+            "no_fishing" => Some(AreaSymbolRule::Fixed(AreaSymbol::FishingProhibited)),
+            "caution" => Some(AreaSymbolRule::Fixed(AreaSymbol::Caution)),
+            "information" => Some(AreaSymbolRule::Fixed(AreaSymbol::Information)),
+            "restrictions" => Some(AreaSymbolRule::Restrictions),
+            _ => None,
+        }
+    }
+}
+
+/// RESARE02: entry restrictions matter most, then anchoring, then fishing. Otherwise areas that are
+/// a hazard or busy get the caution symbol and the rest, like nature reserves, an information one
+pub fn symbol_for_restrictions(restrictions: &[Restriction], categories: &[RestrictedAreaCategory]) -> AreaSymbol {
+    use Restriction::*;
+    use RestrictedAreaCategory::*;
+    let has = |wanted: &[Restriction]| restrictions.iter().any(|x| wanted.contains(x));
+    if has(&[EntryProhibited, EntryRestricted]) {
+        AreaSymbol::EntryProhibited
+    }
+    else if has(&[AnchoringProhibited, AnchoringRestricted]) {
+        AreaSymbol::AnchoringProhibited
+    }
+    else if has(&[FishingProhibited, FishingRestricted, TrawlingProhibited, TrawlingRestricted]) {
+        AreaSymbol::FishingProhibited
+    }
+    else if categories.iter().any(|x| {
+        matches!(x, OffshoreSafetyZone | DegaussingRange | MilitaryArea | NavigationalAidSafetyZone | Minefield | SwimmingArea | WaitingArea | DredgingArea | NoWakeArea | SwingingArea | WaterSkiingArea)
+    }) {
+        AreaSymbol::Caution
+    }
+    else {
+        AreaSymbol::Information
+    }
+}
+
+/// How an area layer looks, from its [LAYER] table or the built in defaults
+#[derive(Clone, Debug, PartialEq)]
+pub struct AreaStyle {
+    pub fill: AreaFill,
+    // how much of the fill colour shows, 0 to 1
+    pub opacity: f32,
+    // draw the edges of the areas with the layer's line style
+    pub boundary: bool,
+    // pixels between hatch lines and their angle in degrees counterclockwise from east
+    pub hatch_spacing: f32,
+    pub hatch_angle: f32,
+    pub symbol: Option<AreaSymbolRule>,
+}
+
+impl Default for AreaStyle {
+    fn default() -> AreaStyle {
+        AreaStyle { fill: AreaFill::Solid, opacity: 1.0, boundary: false, hatch_spacing: 10.0, hatch_angle: 45.0, symbol: None }
+    }
+}
+
+impl AreaStyle {
+    /// areas that let what's under them show are drawn after the depth areas instead of before
+    pub fn is_overlay(&self) -> bool {
+        self.fill != AreaFill::Solid || self.opacity < 1.0
+    }
+}
+
+/// where each area of a layer gets its symbol, a spot inside the area even when it's an odd shape
+pub fn get_area_symbols(ds: &Dataset, layer_name: &str, projection: &dyn Projection, rule: AreaSymbolRule) -> Vec<(Vector2f, AreaSymbol)> {
+    let mut symbols = Vec::new();
+    for mut layer in get_layers(ds, vec![layer_name]) {
+        let mut stats = ConversionStats::default();
+        for feature in layer.features() {
+            let geometry = match stats.convert(feature.geometry()) {
+                Some(geometry) => project_geometry(geometry, projection),
+                None => continue,
+            };
+            // lines and points in an area layer don't get one
+            if !matches!(geometry, geo::Geometry::Polygon(_) | geo::Geometry::MultiPolygon(_)) {
+                continue;
+            }
+            let point = match geometry.interior_point() {
+                Some(point) => Vector2f::new(point.x() as f32, point.y() as f32),
+                None => continue,
+            };
+            let symbol = match rule {
+                AreaSymbolRule::Fixed(symbol) => symbol,
+                AreaSymbolRule::Restrictions => {
+                    let attributes = Attributes::new(&feature);
+                    symbol_for_restrictions(&attributes.list(), &attributes.list())
+                }
+            };
+            symbols.push((point, symbol));
+        }
+    }
+    symbols
+}
+
+/// Liang–Barsky, the part of a line inside an extent
+fn clip_segment(a: Vector2f, b: Vector2f, extent: &LayerExtent) -> Option<(Vector2f, Vector2f)> {
+    let d = b - a;
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in [(-d.x, a.x - extent.MinX), (d.x, extent.MaxX - a.x), (-d.y, a.y - extent.MinY), (d.y, extent.MaxY - a.y)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        }
+        else {
+            t1 = t1.min(t);
+        }
+    }
+    if t0 > t1 { None } else { Some((a + d * t0, a + d * t1)) }
+}
+
+/// Hatch lines `spacing` world units apart across polygons, cut to `extent`. Lines sit at multiples of the
+/// spacing from the origin so neighbouring areas and rebuilt hatches line up.
+pub fn hatch_lines(polygons: &[Polygon], spacing: f64, angle: f64, extent: &LayerExtent) -> Vec<(Vector2f, Vector2f)> {
+    // y is down on screen so counterclockwise from east goes up
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let direction = (cos, -sin);
+    let normal = (sin, cos);
+    let along = |x: f64, y: f64| x * direction.0 + y * direction.1;
+    let across = |x: f64, y: f64| x * normal.0 + y * normal.1;
+    let corners = [(extent.MinX, extent.MinY), (extent.MaxX, extent.MinY), (extent.MinX, extent.MaxY), (extent.MaxX, extent.MaxY)];
+    let view_across = corners.iter().map(|(x, y)| across(*x as f64, *y as f64));
+    let (view_min, view_max) = view_across.fold((f64::MAX, f64::MIN), |(min, max), x| (min.min(x), max.max(x)));
+
+    let mut segments = Vec::new();
+    for polygon in polygons {
+        let edges: Vec<_> = std::iter::once(polygon.exterior()).chain(polygon.interiors()).flat_map(|x| x.lines()).collect();
+        let (min, max) = edges.iter().map(|x| across(x.start.x, x.start.y)).fold((f64::MAX, f64::MIN), |(min, max), x| (min.min(x), max.max(x)));
+        let first = (min.max(view_min) / spacing).ceil() as i64;
+        let last = (max.min(view_max) / spacing).floor() as i64;
+        for k in first..=last {
+            let offset = k as f64 * spacing;
+            let mut crossings: Vec<f64> = edges
+                .iter()
+                .filter_map(|edge| {
+                    let (a, b) = (across(edge.start.x, edge.start.y) - offset, across(edge.end.x, edge.end.y) - offset);
+                    if (a > 0.0) == (b > 0.0) {
+                        return None;
+                    }
+                    let t = a / (a - b);
+                    Some(along(edge.start.x + (edge.end.x - edge.start.x) * t, edge.start.y + (edge.end.y - edge.start.y) * t))
+                })
+                .collect();
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            // even-odd, inside between each pair of crossings
+            for pair in crossings.chunks_exact(2) {
+                let point = |u: f64| Vector2f::new((normal.0 * offset + direction.0 * u) as f32, (normal.1 * offset + direction.1 * u) as f32);
+                segments.extend(clip_segment(point(pair[0]), point(pair[1]), extent));
+            }
+        }
+    }
+    segments
+}
+
+/// The hatching of a layer, rebuilt when the zoom changes the spacing or the view moves off what was built.
+/// Spacing is rounded to a power of two in world units so it only changes every so often while zooming.
+#[derive(Default)]
+pub struct Hatch {
+    // world spacing, the extent it covers and its triangles
+    cache: RefCell<Option<(f64, LayerExtent, Vec<Vertex>)>>,
+}
+
+impl Hatch {
+    pub fn render(&self, window: &mut dyn RenderTarget, view: &View, polygons: &[Polygon], style: &AreaStyle, color: Color, world_per_pixel: f32) {
+        let spacing = 2f64.powf(((style.hatch_spacing * world_per_pixel) as f64).log2().round());
+        let view_extent = get_view_extent(view);
+        let mut cache = self.cache.borrow_mut();
+        let stale = match &*cache {
+            Some((built_for, covered, _)) => {
+                *built_for != spacing || view_extent.MinX < covered.MinX || view_extent.MaxX > covered.MaxX || view_extent.MinY < covered.MinY || view_extent.MaxY > covered.MaxY
+            }
+            None => true,
+        };
+        if stale {
+            // a view's worth either side so panning doesn't rebuild it straight away
+            let (width, height) = (view_extent.MaxX - view_extent.MinX, view_extent.MaxY - view_extent.MinY);
+            let covered = LayerExtent { MinX: view_extent.MinX - width, MaxX: view_extent.MaxX + width, MinY: view_extent.MinY - height, MaxY: view_extent.MaxY + height };
+            // the line keeps the same width compared to the spacing as it had when it was asked for
+            let half_width = HATCH_WIDTH / 2.0 * spacing as f32 / style.hatch_spacing;
+            let mut vertices = Vec::new();
+            for (a, b) in hatch_lines(polygons, spacing, style.hatch_angle as f64, &covered) {
+                tessellate_polyline(&[a, b], half_width, LineCap::Butt, color, &mut vertices);
+            }
+            *cache = Some((spacing, covered, vertices));
+        }
+        if let Some((_, _, vertices)) = &*cache {
+            window.draw_primitives(vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+        }
+    }
+}
+
+/// the symbols in the middle of regulatory areas, like the anchor of an anchorage
+pub fn render_area_symbols(window: &mut dyn RenderTarget, symbols: &[(Vector2f, AreaSymbol)], color: Color, zoom: f32, view: &View) {
+    if symbols.is_empty() || !Symbology::AreaSymbols.shown(zoom) {
+        return;
+    }
+    let view = get_view_extent(view);
+    let margin = 10.0 * zoom;
+    // the symbol is drawn solid even when the area's fill is see-through
+    let color = Color::rgb(color.r, color.g, color.b);
+    let mut vertices = Vec::new();
+    for (p, symbol) in symbols {
+        if !visible(*p, margin, &view) {
+            continue;
+        }
+        for stroke in symbol.strokes() {
+            let stroke: Vec<Vector2f> = stroke.iter().map(|x| *p + *x * zoom).collect();
+            tessellate_polyline(&stroke, 0.75 * zoom, LineCap::Round, color, &mut vertices);
+        }
+    }
+    window.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+}
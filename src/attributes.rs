@@ -0,0 +1,450 @@
+use gdal::vector::Feature;
+
+/// How an attribute is encoded, from the S-57 attribute catalogue. GDAL hands every one of them back
+/// as text, lists look like "(2:1,3)", a count then the values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeKind {
+    // E, one code from a table
+    Enumeration,
+    // L, several codes from a table
+    List,
+    // I
+    Integer,
+    // F
+    Float,
+    // A and S, free text
+    String,
+}
+
+/// the kind of the attributes we read, anything else is taken as text
+pub fn attribute_kind(acronym: &str) -> AttributeKind {
+    match acronym {
+        "BCNSHP" | "BOYSHP" | "CATCAM" | "CATLAM" | "CATNAV" | "CATTRK" | "CATWRK" | "LITCHR" | "TOPSHP" | "TRAFIC" | "WATLEV" => AttributeKind::Enumeration,
+        "CATLIT" | "CATREA" | "COLOUR" | "COLPAT" | "QUASOU" | "RESTRN" => AttributeKind::List,
+        "SCAMIN" | "SCAMAX" => AttributeKind::Integer,
+        "DRVAL1" | "DRVAL2" | "ORIENT" | "SECTR1" | "SECTR2" | "SIGPER" | "VALDCO" | "VALNMR" | "VALSOU" => AttributeKind::Float,
+        _ => AttributeKind::String,
+    }
+}
+
+/// A decoded attribute value
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    Enumeration(u32),
+    List(Vec<u32>),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl AttributeValue {
+    /// None for empty values, S-57 leaves attributes blank when they aren't known
+    pub fn parse(kind: AttributeKind, raw: &str) -> Option<AttributeValue> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return None;
+        }
+        match kind {
+            // an enumeration sometimes comes through as a list of one
+            AttributeKind::Enumeration => parse_list(raw).first().map(|x| AttributeValue::Enumeration(*x)),
+            AttributeKind::List => Some(parse_list(raw)).filter(|x| !x.is_empty()).map(AttributeValue::List),
+            AttributeKind::Integer => raw.parse().ok().map(AttributeValue::Integer),
+            AttributeKind::Float => raw.parse().ok().map(AttributeValue::Float),
+            AttributeKind::String => Some(AttributeValue::String(raw.to_string())),
+        }
+    }
+}
+
+/// codes out of "(2:1,3)", "(1,3)" or "1,3"
+pub fn parse_list(value: &str) -> Vec<u32> {
+    let value = value.trim().trim_start_matches('(').trim_end_matches(')');
+    let values = value.split_once(':').map_or(value, |(_, values)| values);
+    values.split(',').filter_map(|x| x.trim().parse().ok()).collect()
+}
+
+/// An S-57 enumeration table, decoded from its attribute's codes
+pub trait AttributeEnum: Sized {
+    const ACRONYM: &'static str;
+    fn from_code(code: u32) -> Option<Self>;
+    /// the code the chart gives for this value
+    fn code(self) -> u32;
+}
+
+/// Typed access to the attributes of one feature, for the object loaders
+pub struct Attributes<'a> {
+    feature: &'a Feature<'a>,
+}
+
+impl<'a> Attributes<'a> {
+    pub fn new(feature: &'a Feature<'a>) -> Attributes<'a> {
+        Attributes { feature }
+    }
+
+    /// None when the layer doesn't have the attribute or this feature leaves it blank
+    pub fn get(&self, acronym: &str) -> Option<AttributeValue> {
+        let raw = self.feature.field_as_string_by_name(acronym).ok().flatten()?;
+        AttributeValue::parse(attribute_kind(acronym), &raw)
+    }
+
+    pub fn float(&self, acronym: &str) -> Option<f64> {
+        match self.get(acronym)? {
+            AttributeValue::Float(x) => Some(x),
+            AttributeValue::Integer(x) => Some(x as f64),
+            AttributeValue::Enumeration(x) => Some(x as f64),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, acronym: &str) -> Option<String> {
+        match self.get(acronym)? {
+            AttributeValue::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// the codes of an enumeration or list attribute
+    pub fn codes(&self, acronym: &str) -> Vec<u32> {
+        match self.get(acronym) {
+            Some(AttributeValue::Enumeration(x)) => vec![x],
+            Some(AttributeValue::List(x)) => x,
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn enumeration<T: AttributeEnum>(&self) -> Option<T> {
+        self.codes(T::ACRONYM).first().and_then(|x| T::from_code(*x))
+    }
+
+    /// every value of a list attribute in the order given, codes we don't know are skipped
+    pub fn list<T: AttributeEnum>(&self) -> Vec<T> {
+        self.codes(T::ACRONYM).into_iter().filter_map(T::from_code).collect()
+    }
+}
+
+/// writes out an enumeration table as a Rust enum with its codes
+macro_rules! attribute_enum {
+    ($(#[$meta:meta])* $name:ident, $acronym:literal { $($variant:ident = $code:literal,)+ }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)+
+        }
+
+        impl AttributeEnum for $name {
+            const ACRONYM: &'static str = $acronym;
+
+            fn from_code(code: u32) -> Option<$name> {
+                match code {
+                    $($code => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+
+            fn code(self) -> u32 {
+                match self {
+                    $($name::$variant => $code,)+
+                }
+            }
+        }
+    };
+}
+
+attribute_enum!(
+    /// COLOUR
+    Colour, "COLOUR" {
+        White = 1,
+        Black = 2,
+        Red = 3,
+        Green = 4,
+        Blue = 5,
+        Yellow = 6,
+        Grey = 7,
+        Brown = 8,
+        Amber = 9,
+        Violet = 10,
+        Orange = 11,
+        Magenta = 12,
+        Pink = 13,
+    }
+);
+
+attribute_enum!(
+    /// COLPAT, how the colours of a mark are laid out
+    ColourPattern, "COLPAT" {
+        HorizontalBands = 1,
+        VerticalStripes = 2,
+        DiagonalStripes = 3,
+        Squared = 4,
+        Stripes = 5,
+        BorderStripe = 6,
+    }
+);
+
+attribute_enum!(
+    /// CATLAM, category of lateral mark
+    LateralCategory, "CATLAM" {
+        Port = 1,
+        Starboard = 2,
+        PreferredChannelToStarboard = 3,
+        PreferredChannelToPort = 4,
+    }
+);
+
+attribute_enum!(
+    /// CATCAM, category of cardinal mark: which side of the danger to pass
+    CardinalCategory, "CATCAM" {
+        North = 1,
+        East = 2,
+        South = 3,
+        West = 4,
+    }
+);
+
+attribute_enum!(
+    /// BOYSHP
+    BuoyShape, "BOYSHP" {
+        Conical = 1,
+        Can = 2,
+        Spherical = 3,
+        Pillar = 4,
+        Spar = 5,
+        Barrel = 6,
+        SuperBuoy = 7,
+        IceBuoy = 8,
+    }
+);
+
+attribute_enum!(
+    /// BCNSHP
+    BeaconShape, "BCNSHP" {
+        Stake = 1,
+        Withy = 2,
+        Tower = 3,
+        Lattice = 4,
+        Pile = 5,
+        Cairn = 6,
+        Buoyant = 7,
+    }
+);
+
+attribute_enum!(
+    /// LITCHR, light characteristic
+    LightCharacter, "LITCHR" {
+        Fixed = 1,
+        Flashing = 2,
+        LongFlashing = 3,
+        QuickFlashing = 4,
+        VeryQuickFlashing = 5,
+        UltraQuickFlashing = 6,
+        Isophased = 7,
+        Occulting = 8,
+        InterruptedQuickFlashing = 9,
+        InterruptedVeryQuickFlashing = 10,
+        InterruptedUltraQuickFlashing = 11,
+        Morse = 12,
+        FixedAndFlashing = 13,
+        FlashAndLongFlash = 14,
+        OccultingAndFlash = 15,
+        FixedAndLongFlash = 16,
+        AlternatingOcculting = 17,
+        AlternatingLongFlashing = 18,
+        AlternatingFlashing = 19,
+        AlternatingGroup = 20,
+        QuickFlashPlusLongFlash = 25,
+        VeryQuickFlashPlusLongFlash = 26,
+        UltraQuickFlashPlusLongFlash = 27,
+        Alternating = 28,
+        FixedAndAlternatingFlashing = 29,
+    }
+);
+
+attribute_enum!(
+    /// WATLEV, how the water covers an object
+    WaterLevel, "WATLEV" {
+        PartlySubmerged = 1,
+        AlwaysDry = 2,
+        AlwaysSubmerged = 3,
+        CoversAndUncovers = 4,
+        Awash = 5,
+        SubjectToFlooding = 6,
+        Floating = 7,
+    }
+);
+
+attribute_enum!(
+    /// CATWRK, category of wreck
+    WreckCategory, "CATWRK" {
+        NonDangerous = 1,
+        Dangerous = 2,
+        DistributedRemains = 3,
+        MastShowing = 4,
+        HullShowing = 5,
+    }
+);
+
+attribute_enum!(
+    /// QUASOU, quality of a sounding or VALSOU
+    SoundingQuality, "QUASOU" {
+        DepthKnown = 1,
+        DepthUnknown = 2,
+        Doubtful = 3,
+        Unreliable = 4,
+        NoBottomFound = 5,
+        LeastDepthKnown = 6,
+        LeastDepthUnknown = 7,
+        ReportedNotSurveyed = 8,
+        ReportedNotConfirmed = 9,
+        Maintained = 10,
+        NotRegularlyMaintained = 11,
+    }
+);
+
+attribute_enum!(
+    /// RESTRN, what isn't allowed in an area
+    Restriction, "RESTRN" {
+        AnchoringProhibited = 1,
+        AnchoringRestricted = 2,
+        FishingProhibited = 3,
+        FishingRestricted = 4,
+        TrawlingProhibited = 5,
+        TrawlingRestricted = 6,
+        EntryProhibited = 7,
+        EntryRestricted = 8,
+        DredgingProhibited = 9,
+        DredgingRestricted = 10,
+        DivingProhibited = 11,
+        DivingRestricted = 12,
+        NoWake = 13,
+        AreaToBeAvoided = 14,
+        ConstructionProhibited = 15,
+        DischargingProhibited = 16,
+        DischargingRestricted = 17,
+        ExplorationProhibited = 18,
+        ExplorationRestricted = 19,
+        DrillingProhibited = 20,
+        DrillingRestricted = 21,
+        RemovalOfArtifactsProhibited = 22,
+        LighteringProhibited = 23,
+        DraggingProhibited = 24,
+        StoppingProhibited = 25,
+        LandingProhibited = 26,
+        SpeedRestricted = 27,
+    }
+);
+
+attribute_enum!(
+    /// CATREA, category of restricted area
+    RestrictedAreaCategory, "CATREA" {
+        OffshoreSafetyZone = 1,
+        NatureReserve = 4,
+        BirdSanctuary = 5,
+        GameReserve = 6,
+        SealSanctuary = 7,
+        DegaussingRange = 8,
+        MilitaryArea = 9,
+        HistoricWreckArea = 10,
+        NavigationalAidSafetyZone = 12,
+        Minefield = 14,
+        SwimmingArea = 18,
+        WaitingArea = 19,
+        ResearchArea = 20,
+        DredgingArea = 21,
+        FishSanctuary = 22,
+        EcologicalReserve = 23,
+        NoWakeArea = 24,
+        SwingingArea = 25,
+        WaterSkiingArea = 26,
+        EnvironmentallySensitiveSeaArea = 27,
+        ParticularlySensitiveSeaArea = 28,
+    }
+);
+
+attribute_enum!(
+    /// TRAFIC, which way traffic goes along a route
+    TrafficFlow, "TRAFIC" {
+        Inbound = 1,
+        Outbound = 2,
+        OneWay = 3,
+        TwoWay = 4,
+    }
+);
+
+attribute_enum!(
+    /// CATTRK, category of recommended track
+    TrackCategory, "CATTRK" {
+        FixedMarks = 1,
+        NotFixedMarks = 2,
+    }
+);
+
+attribute_enum!(
+    /// CATNAV, category of navigation line
+    NavigationLineCategory, "CATNAV" {
+        Clearing = 1,
+        Transit = 2,
+        Leading = 3,
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists() {
+        assert_eq!(parse_list("(2:1,3)"), vec![1, 3]);
+        assert_eq!(parse_list("(1,3)"), vec![1, 3]);
+        assert_eq!(parse_list("1,3"), vec![1, 3]);
+        assert_eq!(parse_list(" 4 "), vec![4]);
+        assert_eq!(parse_list(""), Vec::<u32>::new());
+        assert_eq!(parse_list("(0:)"), Vec::<u32>::new());
+        assert_eq!(AttributeValue::parse(AttributeKind::List, "(2:1,3)"), Some(AttributeValue::List(vec![1, 3])));
+    }
+
+    #[test]
+    fn blank_values() {
+        for kind in [AttributeKind::Enumeration, AttributeKind::List, AttributeKind::Integer, AttributeKind::Float, AttributeKind::String] {
+            assert_eq!(AttributeValue::parse(kind, ""), None);
+            assert_eq!(AttributeValue::parse(kind, "  "), None);
+        }
+        // a list with nothing in it is as good as blank
+        assert_eq!(AttributeValue::parse(AttributeKind::List, "()"), None);
+        assert_eq!(AttributeValue::parse(AttributeKind::List, "(0:)"), None);
+        assert_eq!(AttributeValue::parse(AttributeKind::Enumeration, "()"), None);
+    }
+
+    #[test]
+    fn enumeration_as_a_list_of_one() {
+        assert_eq!(AttributeValue::parse(AttributeKind::Enumeration, "(1:3)"), Some(AttributeValue::Enumeration(3)));
+        assert_eq!(AttributeValue::parse(AttributeKind::Enumeration, "3"), Some(AttributeValue::Enumeration(3)));
+        assert_eq!(AttributeValue::parse(attribute_kind("WATLEV"), "(1:3)"), Some(AttributeValue::Enumeration(3)));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(AttributeValue::parse(AttributeKind::Integer, "12000"), Some(AttributeValue::Integer(12000)));
+        // a fraction isn't rounded into an integer attribute
+        assert_eq!(AttributeValue::parse(AttributeKind::Integer, "2.5"), None);
+        assert_eq!(AttributeValue::parse(AttributeKind::Float, "2.5"), Some(AttributeValue::Float(2.5)));
+        assert_eq!(AttributeValue::parse(AttributeKind::Float, "12"), Some(AttributeValue::Float(12.0)));
+        assert_eq!(AttributeValue::parse(AttributeKind::Float, "deep"), None);
+        assert_eq!(AttributeValue::parse(attribute_kind("OBJNAM"), " Chico "), Some(AttributeValue::String("Chico".to_string())));
+    }
+
+    #[test]
+    fn unknown_codes() {
+        assert_eq!(Colour::from_code(3), Some(Colour::Red));
+        assert_eq!(Colour::from_code(0), None);
+        assert_eq!(Colour::from_code(14), None);
+        assert_eq!(WaterLevel::from_code(3), Some(WaterLevel::AlwaysSubmerged));
+        assert_eq!(WaterLevel::from_code(8), None);
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        for code in 1..=13 {
+            assert_eq!(Colour::from_code(code).map(Colour::code), Some(code));
+        }
+        assert_eq!(WaterLevel::AlwaysSubmerged.code(), 3);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use gdal::vector::{Feature, Layer, LayerAccess};
+use gdal::Dataset;
+use log::{debug, warn};
+use sfml::graphics::{Color, PrimitiveType, RenderStates, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+
+use crate::attributes::{Attributes, BeaconShape, BuoyShape, CardinalCategory, Colour, ColourPattern, LateralCategory};
+use crate::geometry::get_layers;
+use crate::line::{tessellate_polyline, LineCap};
+use crate::render::{get_view_extent, push_convex, visible, Symbology};
+use crate::transform::Projection;
+
+/// Buoy and beacon object classes we draw
+pub const NAV_AID_LAYERS: [&str; 11] = ["BOYCAR", "BOYINB", "BOYISD", "BOYLAT", "BOYSAW", "BOYSPP", "BCNCAR", "BCNISD", "BCNLAT", "BCNSAW", "BCNSPP"];
+
+pub const IALA_REGION_NAMES: [&str; 2] = ["A", "B"];
+
+// marks the chart doesn't give a colour are drawn in magenta so they stand out as unknown
+const UNKNOWN_COLOUR: Color = Color::rgb(197, 69, 195);
+
+/// IALA buoyage region, which side red goes on when coming in from sea
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IalaRegion {
+    // red to port, Europe, Africa, most of Asia and Australia
+    A,
+    // green to port, the Americas, Japan, Korea and the Philippines
+    B,
+}
+
+impl IalaRegion {
+    pub fn from_name(name: &str) -> Option<IalaRegion> {
+        match name.to_uppercase().as_str() {
+            "A" => Some(IalaRegion::A),
+            "B" => Some(IalaRegion::B),
+            _ => None,
+        }
+    }
+
+    /// colours of port and starboard hand lateral marks
+    fn lateral_colours(&self) -> (Colour, Colour) {
+        match self {
+            IalaRegion::A => (Colour::Red, Colour::Green),
+            IalaRegion::B => (Colour::Green, Colour::Red),
+        }
+    }
+}
+
+/// something to draw a COLOUR with
+pub fn colour_color(colour: Colour) -> Color {
+    match colour {
+        Colour::White => Color::WHITE,
+        Colour::Black => Color::BLACK,
+        Colour::Red => Color::rgb(237, 28, 36),
+        Colour::Green => Color::rgb(0, 166, 81),
+        Colour::Blue => Color::rgb(0, 84, 166),
+        Colour::Yellow => Color::rgb(255, 214, 0),
+        Colour::Grey => Color::rgb(128, 128, 128),
+        Colour::Brown => Color::rgb(139, 90, 43),
+        Colour::Amber => Color::rgb(255, 191, 0),
+        Colour::Violet => Color::rgb(143, 0, 255),
+        Colour::Orange => Color::rgb(255, 127, 0),
+        Colour::Magenta => Color::rgb(197, 69, 195),
+        Colour::Pink => Color::rgb(255, 170, 200),
+    }
+}
+
+/// Body shapes from BOYSHP and BCNSHP
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkShape {
+    Conical,
+    Can,
+    Spherical,
+    Pillar,
+    Spar,
+    Barrel,
+    SuperBuoy,
+    Stake,
+    Tower,
+    Lattice,
+    Pile,
+    Cairn,
+}
+
+impl MarkShape {
+    pub fn for_buoy(shape: BuoyShape) -> MarkShape {
+        match shape {
+            BuoyShape::Conical => MarkShape::Conical,
+            BuoyShape::Can => MarkShape::Can,
+            BuoyShape::Spherical => MarkShape::Spherical,
+            BuoyShape::Pillar => MarkShape::Pillar,
+            BuoyShape::Spar => MarkShape::Spar,
+            BuoyShape::Barrel => MarkShape::Barrel,
+            BuoyShape::SuperBuoy => MarkShape::SuperBuoy,
+            // ice buoys look like pillars on a chart
+            BuoyShape::IceBuoy => MarkShape::Pillar,
+        }
+    }
+
+    pub fn for_beacon(shape: BeaconShape) -> MarkShape {
+        match shape {
+            // stake, pole, perch or post and withy
+            BeaconShape::Stake | BeaconShape::Withy => MarkShape::Stake,
+            BeaconShape::Tower => MarkShape::Tower,
+            BeaconShape::Lattice => MarkShape::Lattice,
+            BeaconShape::Pile => MarkShape::Pile,
+            BeaconShape::Cairn => MarkShape::Cairn,
+            // buoyant beacons float like a pillar buoy
+            BeaconShape::Buoyant => MarkShape::Pillar,
+        }
+    }
+
+    /// convex outline in symbol units, x right and y down from the charted position at the middle of the base
+    pub fn outline(&self) -> Vec<Vector2f> {
+        let points: &[(f32, f32)] = match self {
+            MarkShape::Conical => &[(-6.0, 0.0), (6.0, 0.0), (0.0, -14.0)],
+            MarkShape::Can => &[(-5.0, 0.0), (5.0, 0.0), (5.0, -12.0), (-5.0, -12.0)],
+            MarkShape::Spherical => return circle(Vector2f::new(0.0, -6.0), 6.0),
+            MarkShape::Pillar => &[(-6.0, 0.0), (6.0, 0.0), (2.0, -16.0), (-2.0, -16.0)],
+            MarkShape::Spar => &[(-1.5, 0.0), (1.5, 0.0), (1.5, -18.0), (-1.5, -18.0)],
+            MarkShape::Barrel => &[(-4.0, 0.0), (4.0, 0.0), (6.0, -4.0), (4.0, -8.0), (-4.0, -8.0), (-6.0, -4.0)],
+            MarkShape::SuperBuoy => &[(-9.0, 0.0), (9.0, 0.0), (7.0, -8.0), (-7.0, -8.0)],
+            MarkShape::Stake => &[(-1.0, 0.0), (1.0, 0.0), (1.0, -16.0), (-1.0, -16.0)],
+            MarkShape::Tower => &[(-5.0, 0.0), (5.0, 0.0), (3.0, -16.0), (-3.0, -16.0)],
+            MarkShape::Lattice => &[(-6.0, 0.0), (6.0, 0.0), (1.0, -16.0), (-1.0, -16.0)],
+            MarkShape::Pile => &[(-2.0, 0.0), (2.0, 0.0), (2.0, -12.0), (-2.0, -12.0)],
+            MarkShape::Cairn => &[(-6.0, 0.0), (6.0, 0.0), (2.0, -8.0), (-2.0, -8.0)],
+        };
+        points.iter().map(|(x, y)| Vector2f::new(*x, *y)).collect()
+    }
+}
+
+/// Topmark shapes from TOPSHP, the ones that mean something for buoyage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TopmarkShape {
+    ConeUp,
+    ConeDown,
+    Sphere,
+    TwoSpheres,
+    Can,
+    Cross,
+    UprightCross,
+    // cardinal marks
+    TwoConesUp,
+    TwoConesDown,
+    TwoConesBaseToBase,
+    TwoConesPointToPoint,
+    Board,
+}
+
+impl TopmarkShape {
+    pub fn from_topshp(value: u32) -> Option<TopmarkShape> {
+        match value {
+            1 => Some(TopmarkShape::ConeUp),
+            2 => Some(TopmarkShape::ConeDown),
+            3 => Some(TopmarkShape::Sphere),
+            4 => Some(TopmarkShape::TwoSpheres),
+            5 => Some(TopmarkShape::Can),
+            6 => Some(TopmarkShape::Board),
+            7 => Some(TopmarkShape::Cross),
+            8 => Some(TopmarkShape::UprightCross),
+            10 => Some(TopmarkShape::TwoConesPointToPoint),
+            11 => Some(TopmarkShape::TwoConesBaseToBase),
+            13 => Some(TopmarkShape::TwoConesUp),
+            14 => Some(TopmarkShape::TwoConesDown),
+            _ => None,
+        }
+    }
+
+    fn for_cardinal(category: CardinalCategory) -> TopmarkShape {
+        match category {
+            CardinalCategory::North => TopmarkShape::TwoConesUp,
+            CardinalCategory::East => TopmarkShape::TwoConesBaseToBase,
+            CardinalCategory::South => TopmarkShape::TwoConesDown,
+            CardinalCategory::West => TopmarkShape::TwoConesPointToPoint,
+        }
+    }
+
+    /// convex pieces in symbol units, sitting on top of a mast that ends at the origin
+    pub fn pieces(&self) -> Vec<Vec<Vector2f>> {
+        let cone_up = |base: f32| vec![Vector2f::new(-4.0, base), Vector2f::new(4.0, base), Vector2f::new(0.0, base - 6.0)];
+        let cone_down = |base: f32| vec![Vector2f::new(-4.0, base - 6.0), Vector2f::new(4.0, base - 6.0), Vector2f::new(0.0, base)];
+        let bar = |angle: f32| {
+            let (along, across) = (Vector2f::new(angle.cos(), angle.sin()) * 4.5, Vector2f::new(-angle.sin(), angle.cos()) * 0.8);
+            let middle = Vector2f::new(0.0, -4.5);
+            vec![middle - along - across, middle + along - across, middle + along + across, middle - along + across]
+        };
+        match self {
+            TopmarkShape::ConeUp => vec![cone_up(0.0)],
+            TopmarkShape::ConeDown => vec![cone_down(0.0)],
+            TopmarkShape::Sphere => vec![circle(Vector2f::new(0.0, -3.5), 3.5)],
+            TopmarkShape::TwoSpheres => vec![circle(Vector2f::new(0.0, -3.0), 3.0), circle(Vector2f::new(0.0, -9.0), 3.0)],
+            TopmarkShape::Can => vec![vec![Vector2f::new(-3.0, 0.0), Vector2f::new(3.0, 0.0), Vector2f::new(3.0, -6.0), Vector2f::new(-3.0, -6.0)]],
+            TopmarkShape::Board => vec![vec![Vector2f::new(-4.0, 0.0), Vector2f::new(4.0, 0.0), Vector2f::new(4.0, -5.0), Vector2f::new(-4.0, -5.0)]],
+            TopmarkShape::Cross => vec![bar(PI / 4.0), bar(-PI / 4.0)],
+            TopmarkShape::UprightCross => vec![bar(0.0), bar(PI / 2.0)],
+            TopmarkShape::TwoConesUp => vec![cone_up(0.0), cone_up(-7.0)],
+            TopmarkShape::TwoConesDown => vec![cone_down(0.0), cone_down(-7.0)],
+            TopmarkShape::TwoConesBaseToBase => vec![cone_down(0.0), cone_up(-6.0)],
+            TopmarkShape::TwoConesPointToPoint => vec![cone_up(0.0), cone_down(-6.0)],
+        }
+    }
+}
+
+fn circle(center: Vector2f, radius: f32) -> Vec<Vector2f> {
+    (0..16).map(|i| i as f32 * PI / 8.0).map(|a| center + Vector2f::new(a.cos(), a.sin()) * radius).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct Topmark {
+    pub shape: TopmarkShape,
+    pub colours: Vec<Colour>,
+}
+
+/// A buoy or beacon
+#[derive(Clone, Debug)]
+pub struct NavAid {
+    // world position
+    pub position: Vector2f,
+    pub shape: MarkShape,
+    // top to bottom, or left to right for vertical stripes
+    pub colours: Vec<Colour>,
+    pub pattern: Option<ColourPattern>,
+    pub topmark: Option<Topmark>,
+}
+
+impl NavAid {
+    /// the body split into its colour bands, each a convex polygon in symbol units
+    pub fn bands(&self) -> Vec<(Vec<Vector2f>, Color)> {
+        let outline = self.shape.outline();
+        if self.colours.len() < 2 {
+            let color = self.colours.first().map_or(UNKNOWN_COLOUR, |x| colour_color(*x));
+            return vec![(outline, color)];
+        }
+        let vertical = self.pattern == Some(ColourPattern::VerticalStripes);
+        let (min, max) = outline.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+            let v = if vertical { p.x } else { p.y };
+            (min.min(v), max.max(v))
+        });
+        let step = (max - min) / self.colours.len() as f32;
+        self.colours
+            .iter()
+            .enumerate()
+            .map(|(i, colour)| {
+                let (from, to) = (min + step * i as f32, min + step * (i + 1) as f32);
+                (clip_band(&outline, vertical, from, to), colour_color(*colour))
+            })
+            .filter(|(band, _)| band.len() >= 3)
+            .collect()
+    }
+}
+
+/// the part of a convex polygon between two x (vertical) or y values
+fn clip_band(polygon: &[Vector2f], vertical: bool, from: f32, to: f32) -> Vec<Vector2f> {
+    let axis = |p: Vector2f| if vertical { p.x } else { p.y };
+    let clip = |points: Vec<Vector2f>, inside: &dyn Fn(f32) -> bool, edge: f32| -> Vec<Vector2f> {
+        let mut out = Vec::new();
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            if inside(axis(a)) {
+                out.push(a);
+            }
+            if inside(axis(a)) != inside(axis(b)) {
+                let t = (edge - axis(a)) / (axis(b) - axis(a));
+                out.push(a + (b - a) * t);
+            }
+        }
+        out
+    };
+    let above = clip(polygon.to_vec(), &|v| v >= from, from);
+    clip(above, &|v| v <= to, to)
+}
+
+fn point_key(position: Vector2f) -> (u32, u32) {
+    (position.x.to_bits(), position.y.to_bits())
+}
+
+/// what a mark looks like when the chart leaves out its shape, colours or topmark,
+/// going by its class, CATCAM/CATLAM and the buoyage region
+fn default_mark(class: &str, catcam: Option<CardinalCategory>, catlam: Option<LateralCategory>, region: IalaRegion) -> (MarkShape, Vec<Colour>, Option<ColourPattern>, Option<TopmarkShape>) {
+    use Colour::*;
+    let beacon = class.starts_with("BCN");
+    let (port, starboard) = region.lateral_colours();
+    let (shape, colours, pattern, topmark) = match &class[3..] {
+        "CAR" => {
+            let colours = match catcam {
+                Some(CardinalCategory::North) => vec![Black, Yellow],
+                Some(CardinalCategory::East) => vec![Black, Yellow, Black],
+                Some(CardinalCategory::South) => vec![Yellow, Black],
+                Some(CardinalCategory::West) => vec![Yellow, Black, Yellow],
+                None => vec![Yellow, Black],
+            };
+            (MarkShape::Pillar, colours, None, catcam.map(TopmarkShape::for_cardinal))
+        }
+        "LAT" => match catlam {
+            Some(LateralCategory::Port) => (MarkShape::Can, vec![port], None, Some(TopmarkShape::Can)),
+            Some(LateralCategory::Starboard) => (MarkShape::Conical, vec![starboard], None, Some(TopmarkShape::ConeUp)),
+            // preferred channel marks take the shape of the side the main channel is to
+            Some(LateralCategory::PreferredChannelToStarboard) => (MarkShape::Can, vec![port, starboard, port], None, Some(TopmarkShape::Can)),
+            Some(LateralCategory::PreferredChannelToPort) => (MarkShape::Conical, vec![starboard, port, starboard], None, Some(TopmarkShape::ConeUp)),
+            None => (MarkShape::Pillar, Vec::new(), None, None),
+        },
+        "ISD" => (MarkShape::Pillar, vec![Black, Red, Black], None, Some(TopmarkShape::TwoSpheres)),
+        "SAW" => (MarkShape::Spherical, vec![Red, White], Some(ColourPattern::VerticalStripes), Some(TopmarkShape::Sphere)),
+        "SPP" => (MarkShape::Pillar, vec![Yellow], None, Some(TopmarkShape::Cross)),
+        _ => (MarkShape::Pillar, Vec::new(), None, None),
+    };
+    if beacon {
+        (MarkShape::Stake, colours, pattern, topmark)
+    }
+    else {
+        (shape, colours, pattern, topmark)
+    }
+}
+
+/// the topmark colour when the chart doesn't give one: black on cardinal and isolated danger marks,
+/// otherwise the body's main colour
+fn default_topmark_colours(topmark: TopmarkShape, body: &[Colour]) -> Vec<Colour> {
+    match topmark {
+        TopmarkShape::TwoConesUp | TopmarkShape::TwoConesDown | TopmarkShape::TwoConesBaseToBase | TopmarkShape::TwoConesPointToPoint | TopmarkShape::TwoSpheres => vec![Colour::Black],
+        _ => body.first().map_or(Vec::new(), |x| vec![*x]),
+    }
+}
+
+/// reads every buoy and beacon in a dataset along with the TOPMAR topmarks sitting on them
+pub fn get_nav_aids(ds: &Dataset, projection: &dyn Projection, region: IalaRegion) -> Vec<NavAid> {
+    let project = |feature: &Feature| {
+        let point = feature.geometry().and_then(|x| x.get_point_vec().pop())?;
+        let (x, y) = projection.forward((point.0, point.1));
+        Some(Vector2f::new(x as f32, y as f32))
+    };
+    // topmarks are their own features at the same spot as the buoy or beacon they're on
+    let mut topmarks: HashMap<(u32, u32), Topmark> = HashMap::new();
+    for mut layer in get_layers(ds, vec!["TOPMAR"]) {
+        for feature in layer.features() {
+            let attributes = Attributes::new(&feature);
+            let (position, shape) = match (project(&feature), attributes.codes("TOPSHP").first().and_then(|x| TopmarkShape::from_topshp(*x))) {
+                (Some(position), Some(shape)) => (position, shape),
+                _ => continue,
+            };
+            topmarks.insert(point_key(position), Topmark { shape, colours: attributes.list() });
+        }
+    }
+
+    let mut aids = Vec::new();
+    for mut layer in get_layers(ds, NAV_AID_LAYERS.to_vec()) {
+        let class = layer.name();
+        aids.extend(get_nav_aids_from_layer(&mut layer, &class, &project, &topmarks, region));
+    }
+    debug!("Found {} buoys and beacons", aids.len());
+    aids
+}
+
+fn get_nav_aids_from_layer(layer: &mut Layer, class: &str, project: &dyn Fn(&Feature) -> Option<Vector2f>, topmarks: &HashMap<(u32, u32), Topmark>, region: IalaRegion) -> Vec<NavAid> {
+    let mut aids = Vec::new();
+    for feature in layer.features() {
+        let position = match project(&feature) {
+            Some(position) => position,
+            None => {
+                warn!("No point found in {class} layer!");
+                continue;
+            }
+        };
+        let attributes = Attributes::new(&feature);
+        let (default_shape, default_colours, default_pattern, default_topmark) = default_mark(class, attributes.enumeration(), attributes.enumeration(), region);
+        let shape = if class.starts_with("BCN") { attributes.enumeration().map(MarkShape::for_beacon) } else { attributes.enumeration().map(MarkShape::for_buoy) };
+        let shape = shape.unwrap_or(default_shape);
+        let mut colours = attributes.list();
+        let mut pattern = attributes.enumeration();
+        if colours.is_empty() {
+            colours = default_colours;
+            pattern = pattern.or(default_pattern);
+        }
+        let topmark = topmarks.get(&point_key(position)).cloned().or(default_topmark.map(|shape| Topmark { shape, colours: Vec::new() })).map(|mut topmark| {
+            if topmark.colours.is_empty() {
+                topmark.colours = default_topmark_colours(topmark.shape, &colours);
+            }
+            topmark
+        });
+        aids.push(NavAid { position, shape, colours, pattern, topmark });
+    }
+    aids
+}
+
+// buoys and beacons are drawn this many pixels to a symbol unit at NAV_AID_REFERENCE_ZOOM world meters per pixel,
+// growing a little as you zoom in and shrinking as you zoom out, within NAV_AID_SCALE_LIMITS
+const NAV_AID_REFERENCE_ZOOM: f32 = 5.0;
+const NAV_AID_SCALE_LIMITS: (f32, f32) = (0.6, 1.6);
+// length of the mast between a body and its topmark, in symbol units
+const TOPMARK_MAST: f32 = 3.0;
+
+fn push_closed_outline(out: &mut Vec<Vertex>, points: &[Vector2f], half_width: f32) {
+    let mut closed = points.to_vec();
+    closed.extend(points.first());
+    tessellate_polyline(&closed, half_width, LineCap::Round, Color::BLACK, out);
+}
+
+/// buoys and beacons as paper chart style symbols: the body's shape in its colour bands with the topmark on a short mast
+pub fn render_nav_aids(window: &mut dyn RenderTarget, aids: &[NavAid], zoom: f32, view: &View) {
+    if !Symbology::NavAids.shown(zoom) {
+        return;
+    }
+    let unit = zoom * (NAV_AID_REFERENCE_ZOOM / zoom).sqrt().clamp(NAV_AID_SCALE_LIMITS.0, NAV_AID_SCALE_LIMITS.1);
+    let view = get_view_extent(view);
+    let margin = 30.0 * unit;
+    let mut vertices = Vec::new();
+    for aid in aids {
+        let p = aid.position;
+        if !visible(p, margin, &view) {
+            continue;
+        }
+        let to_world = |points: &[Vector2f]| -> Vec<Vector2f> { points.iter().map(|x| p + *x * unit).collect() };
+        let outline = aid.shape.outline();
+        if let Some(topmark) = &aid.topmark {
+            let top = outline.iter().map(|x| x.y).fold(0.0, f32::min);
+            let mast_top = Vector2f::new(0.0, top - TOPMARK_MAST);
+            tessellate_polyline(&to_world(&[Vector2f::new(0.0, top), mast_top]), 0.5 * unit, LineCap::Butt, Color::BLACK, &mut vertices);
+            let color = topmark.colours.first().map_or(Color::BLACK, |x| colour_color(*x));
+            for piece in topmark.shape.pieces() {
+                let piece: Vec<Vector2f> = piece.iter().map(|x| *x + mast_top).collect();
+                push_convex(&mut vertices, &to_world(&piece), color);
+                push_closed_outline(&mut vertices, &to_world(&piece), 0.4 * unit);
+            }
+        }
+        for (band, color) in aid.bands() {
+            push_convex(&mut vertices, &to_world(&band), color);
+        }
+        push_closed_outline(&mut vertices, &to_world(&outline), 0.5 * unit);
+    }
+    window.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fs::read_dir;
+
+use gdal::Dataset;
+use gdal::vector::LayerAccess;
+use geo::{HaversineDistance, Point};
+use log::info;
+use sfml::graphics::Font;
+use sfml::SfBox;
+
+use crate::buoys::{get_nav_aids, NavAid};
+use crate::config::Config;
+use crate::depth::{get_contour_features, safety_contour_lines, ContourFeatures, DepthIndex, SafetySettings, DEPSC};
+use crate::hazards::{get_hazards, Hazard};
+use crate::lights::{get_lights, LightStation};
+use crate::routes::{get_routes, get_traffic_arrows, Route, TrafficArrow, ROUTE_LAYERS};
+use crate::line::{LineGeometry, LineStyle};
+use crate::transform::{format_lat_lon, longitude_center, Projection, WorldProjection};
+use crate::text::load_font;
+use crate::units::Units;
+use crate::geometry::{self, get_dataset, get_depare_from_layer, get_depare_layer, get_hashmap_of_depare_layers, get_plotgeo_from_layer_in_dataset, get_soundg_coords, get_soundg_layer, DepthLayer, PlotGeometry, DEPARE};
+
+/// Everything loaded out of the chart directory, projected and ready to draw.
+pub struct ChartSet {
+    pub plot_refs: Vec<PlotGeometry>,
+    pub depare_map: HashMap<u16, Vec<DEPARE>>,
+    // buoys and beacons
+    pub nav_aids: Vec<NavAid>,
+    pub lights: Vec<LightStation>,
+    // recommended tracks, leading lines and the like
+    pub routes: Vec<Route>,
+    // direction of traffic in traffic separation lanes and fairways
+    pub traffic_arrows: Vec<TrafficArrow>,
+    // the soundings and hazards live in here too, it's what they're drawn from
+    pub depth_index: DepthIndex,
+    pub safety: SafetySettings,
+    pub safety_contour: LineGeometry,
+    pub units: Units,
+    // shared by every label drawn over the chart
+    pub font: SfBox<Font>,
+    // what everything above was projected with, for going back to lon/lat
+    pub projection: WorldProjection,
+}
+
+impl ChartSet {
+    /// DRVAL1, DRVAL2 in meters of the depth area at a position
+    pub fn depth_at(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        self.depth_index.depth_at(self.projection.forward((lon, lat)))
+    }
+
+    /// lon/lat, depth in meters and distance away in meters of the closest sounding to a position
+    pub fn nearest_sounding(&self, lat: f64, lon: f64) -> Option<((f64, f64), f64, f64)> {
+        let (point, depth) = self.depth_index.nearest_sounding(self.projection.forward((lon, lat)))?;
+        let sounding = self.projection.inverse(point);
+        let distance = Point::new(lon, lat).haversine_distance(&Point::from(sounding));
+        Some((sounding, depth, distance))
+    }
+
+    /// the closest wreck, obstruction or rock to a position and how far away it is in meters
+    pub fn nearest_hazard(&self, lat: f64, lon: f64) -> Option<(&Hazard, f64)> {
+        let hazard = self.depth_index.nearest_hazard(self.projection.forward((lon, lat)))?;
+        let position = self.projection.inverse((hazard.position.x as f64, hazard.position.y as f64));
+        Some((hazard, Point::new(lon, lat).haversine_distance(&Point::from(position))))
+    }
+
+    /// what the window shows about the spot under the mouse
+    pub fn cursor_readout(&self, lat: f64, lon: f64) -> Vec<String> {
+        let mut lines = vec![format_lat_lon(lat, lon)];
+        let depth = self.units.depth;
+        match self.depth_at(lat, lon) {
+            Some((min, max)) => lines.push(format!("depth {} - {}", depth.format(min), depth.format(max))),
+            None => lines.push("depth unknown".to_string()),
+        }
+        if let Some((_, sounding, distance)) = self.nearest_sounding(lat, lon) {
+            lines.push(format!("nearest sounding {}, {} away", depth.format(sounding), self.units.distance.format(distance)));
+        }
+        if let Some((hazard, distance)) = self.nearest_hazard(lat, lon) {
+            lines.push(format!("nearest hazard {}, {} away", hazard.describe(depth), self.units.distance.format(distance)));
+        }
+        lines
+    }
+}
+
+/// lon/lat in the middle of everything in the datasets, used as the origin of world coordinates.
+/// Cells either side of the antimeridian are centred on it, see longitude_center
+pub fn get_datasets_center(datasets: &[(Dataset, String)]) -> (f64, f64) {
+    let mut longitudes = Vec::new();
+    let (mut min_y, mut max_y) = (f64::MAX, f64::MIN);
+    for (ds, _) in datasets {
+        for layer in ds.layers() {
+            let envelope = match layer.try_get_extent() {
+                Ok(Some(envelope)) => envelope,
+                _ => continue,
+            };
+            longitudes.push((envelope.MinX, envelope.MaxX));
+            (min_y, max_y) = (min_y.min(envelope.MinY), max_y.max(envelope.MaxY));
+        }
+    }
+    match longitude_center(&longitudes) {
+        Some(lon) => (lon, (min_y + max_y) / 2.0),
+        None => (0.0, 0.0),
+    }
+}
+
+// pixels
+const SAFETY_CONTOUR_WIDTH: f32 = 2.5;
+
+pub fn load_chart_set(config: &Config) -> ChartSet {
+    let safety = config.safety.settings();
+    // find the layer names we are interested in
+    let layer_names = config.layers.clone();
+    // get the plotgeos for each layer
+    let mut plotvec: Vec<PlotGeometry> = Vec::new();
+    let chart_dir = read_dir(&config.chart_directory).unwrap();
+
+    let mut plot_refs: Vec<PlotGeometry> = Vec::new();
+    let mut depth_plots: Vec<DepthLayer> = Vec::new();
+    let mut projections: Vec<DepthLayer> = Vec::new();
+    let mut nav_aids = Vec::new();
+    let mut lights = Vec::new();
+    let mut hazards = Vec::new();
+    let mut routes = Vec::new();
+    let mut traffic_arrows = Vec::new();
+    let mut contour_features = ContourFeatures::default();
+
+    let mut paths: Vec<String> = Vec::new();
+
+    for entry in chart_dir {
+        // append all paths to the vector paths
+        let entry = entry.unwrap();
+        let path = entry.path();
+        let path = path.to_str().unwrap();
+        paths.push(path.to_string());
+
+    }
+    let mut datasets: Vec<(Dataset, String)> = Vec::new();
+    for path in paths {
+        let ds = get_dataset(&path);
+        match ds {
+            Ok(ds) => datasets.push((ds, path)),
+            _ => continue,
+        }
+    }
+    let projection = WorldProjection::new(config.projection(), get_datasets_center(&datasets));
+    let mut resolve_depare = Vec::new();
+    for (ds, p) in datasets {
+        // routes are drawn with their own symbology, not as plain lines
+        for layer_name in layer_names.iter().filter(|x| !ROUTE_LAYERS.contains(&x.as_str())) {
+            let layer_color = config.color_for_layer(&layer_name[..]);
+            let plotgeo = get_plotgeo_from_layer_in_dataset(layer_name, &ds, layer_color, config, &projection);
+            plotvec.push(plotgeo);
+        }
+        for mut lights_layer in geometry::get_layers(&ds, vec!["LIGHTS"]) {
+            lights.extend(get_lights(&mut lights_layer, &projection));
+        }
+        nav_aids.extend(get_nav_aids(&ds, &projection, config.iala_region()));
+        hazards.extend(get_hazards(&ds, &projection));
+        routes.extend(get_routes(&ds, &projection, &layer_names));
+        traffic_arrows.extend(get_traffic_arrows(&ds, &projection, &layer_names));
+        contour_features.extend(get_contour_features(&ds, &projection, &safety));
+        let soundg = get_soundg_layer(&ds);
+        let mut soundg_layer = match soundg {
+            Some(soundg) => soundg,
+            _ => continue,
+        };
+        let depth_sounding: DepthLayer = get_soundg_coords(&mut soundg_layer);
+        depth_plots.push(depth_sounding);
+        // update extent if layer's extent is smaller or larger
+        let mut depare_layer = match get_depare_layer(&ds) {
+            Some(depare) => depare,
+            _ => continue,
+        };
+        let depare: DEPARE = get_depare_from_layer(&mut depare_layer, &projection, &safety);
+        resolve_depare.push((depare, p.clone()));
+
+    }
+
+    info!("Handling DEPARE RESOLVING!");
+    let map = get_hashmap_of_depare_layers(resolve_depare);
+    info!("map: {:?}", map.keys());
+
+
+    for mut pg in plotvec {
+    pg.triangulate_and_scale();
+    plot_refs.push(pg);
+    }
+    for mut ds in depth_plots {
+        ds.project_coords(&projection);
+        projections.push(ds);
+    }
+    let depth_index = DepthIndex::new(&map, &projections, hazards, &safety);
+    let safety_contour = LineGeometry::new(safety_contour_lines(&map, &contour_features, &depth_index, &safety), LineStyle::solid(DEPSC, SAFETY_CONTOUR_WIDTH));
+    info!("Safety contour at {}m has {} lines", safety.safety_contour, safety_contour.lines.len());
+    ChartSet { plot_refs, depare_map: map, nav_aids, lights, routes, traffic_arrows, depth_index, safety, safety_contour, units: config.units(), font: load_font(config.display.font.as_deref()), projection }
+}
@@ -1,85 +1,621 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::path::Path;
+
+use serde::Deserialize;
 use sfml::graphics::Color;
-use toml::Table;
-
-pub fn get_config() -> Table {
-    let path = Path::new("config.toml");
-    let mut file = File::open(&path).expect("Couldn't open config.toml");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("Couldn't read config.toml");
-    let config: Table = toml::from_str(&contents).expect("Couldn't parse config.toml");
-    config
-}
-pub fn get_resolution() -> (u32, u32) {
-    let c = get_config();
-    let res1 = c["resolution_1"].as_integer().unwrap() as u32;
-    let res2 = c["resolution_2"].as_integer().unwrap() as u32;
-    (res1, res2)
-}
-
-pub fn get_layers() -> Vec<String> {
-    let c = get_config();
-    let layers = c["layers"].as_array().unwrap();
-    let mut layers_vec = Vec::new();
-    for layer in layers {
-        layers_vec.push(layer.as_str().unwrap().to_string());
-    }
-    layers_vec
-}
-
-pub fn get_color_for_layer(layer_name: &str) -> Color {
-    let c = get_config();
-    let layer_table = c[layer_name].as_table().unwrap();
-    let v = layer_table.get("color").unwrap();
-    let color = {
-        match v {
-            toml::Value::Array(a) => {
-                let r = a[0].as_integer().unwrap() as u8;
-                let g = a[1].as_integer().unwrap() as u8;
-                let b = a[2].as_integer().unwrap() as u8;
-                if a.len() == 3 {
-                    return Color::rgb(r, g, b)
+
+use crate::area::{AreaFill, AreaStyle, AreaSymbolRule, AREA_FILL_NAMES, AREA_SYMBOL_NAMES};
+use crate::buoys::{IalaRegion, IALA_REGION_NAMES};
+use crate::depth::SafetySettings;
+use crate::gps::GpsSource;
+use crate::line::{LineCap, LineStyle};
+use crate::routes::ROUTE_LAYERS;
+use crate::transform::{projection_from_name, Projection, SphericalMercator, PROJECTION_NAMES};
+use crate::units::{DepthUnit, DistanceUnit, SpeedUnit, Units, DEPTH_UNIT_NAMES, DISTANCE_UNIT_NAMES, SPEED_UNIT_NAMES};
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Every object class we know how to load out of an S-57 cell.
+pub const KNOWN_LAYERS: [&str; 26] = [
+    "SEAARE", "RESARE", "COALNE", "LNDARE", "LNDRGN", "DEPARE", "DEPCNT", "FAIRWY", "CBLSUB", "CBLOHD", "PIPSOL", "PIPOHD",
+    "ACHARE", "CTNARE", "MIPARE", "TSSLPT", "PRCARE", "ISTZNE", "CBLARE", "PIPARE", "DMPGRD", "TSEZNE", "TSSBND", "RECTRC",
+    "NAVLNE", "DWRTCL",
+];
+
+// the top level settings in Config, every other top level key has to be a [LAYER] table
+const SETTING_NAMES: [&str; 10] = ["resolution_1", "resolution_2", "chart_directory", "layers", "projection", "iala_region", "gps", "safety", "units", "display"];
+
+/// line width in pixels used when a layer doesn't set one
+pub const DEFAULT_LINE_WIDTH: f32 = 1.0;
+
+/// The whole of config.toml, loaded once at startup and handed to whoever needs it.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub resolution_1: u32,
+    pub resolution_2: u32,
+    pub chart_directory: String,
+    pub layers: Vec<String>,
+    // one of transform::PROJECTION_NAMES
+    pub projection: String,
+    // IALA buoyage region "A" or "B", for the colours of lateral marks the chart doesn't colour itself
+    pub iala_region: String,
+    // [gps], leave it out to run without a position
+    pub gps: Option<GpsConfig>,
+    // [safety], depth shading and the safety contour
+    pub safety: SafetyConfig,
+    // [units] for showing depths, distances and speeds
+    pub units: UnitsConfig,
+    // [display], how often the window redraws
+    pub display: DisplayConfig,
+    // every other top level table is treated as a per layer style, e.g. [LNDARE]
+    #[serde(flatten)]
+    pub layer_styles: HashMap<String, LayerConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LayerConfig {
+    pub color: Option<ColorValue>,
+    // line features, and the boundary of areas when it's drawn
+    pub width: Option<f32>,
+    pub dash: Option<Vec<f32>>,
+    pub cap: Option<String>,
+    // the rest only apply to areas: one of area::AREA_FILL_NAMES
+    pub fill: Option<String>,
+    // 0 to 1, how much of the fill shows
+    pub opacity: Option<f32>,
+    pub boundary: Option<bool>,
+    // pixels between hatch lines and degrees counterclockwise from east
+    pub hatch_spacing: Option<f32>,
+    pub hatch_angle: Option<f32>,
+    // one of area::AREA_SYMBOL_NAMES, drawn in the middle of each area
+    pub symbol: Option<String>,
+}
+
+impl LayerConfig {
+    /// anything this table leaves out comes from `fallback`
+    fn or(&self, fallback: LayerConfig) -> LayerConfig {
+        LayerConfig {
+            color: self.color.clone().or(fallback.color),
+            width: self.width.or(fallback.width),
+            dash: self.dash.clone().or(fallback.dash),
+            cap: self.cap.clone().or(fallback.cap),
+            fill: self.fill.clone().or(fallback.fill),
+            opacity: self.opacity.or(fallback.opacity),
+            boundary: self.boundary.or(fallback.boundary),
+            hatch_spacing: self.hatch_spacing.or(fallback.hatch_spacing),
+            hatch_angle: self.hatch_angle.or(fallback.hatch_angle),
+            symbol: self.symbol.clone().or(fallback.symbol),
+        }
+    }
+}
+
+/// Built in styles for regulatory areas and routing measures. S-52 draws regulatory areas as a dashed
+/// magenta boundary with a symbol in the middle rather than filling them, so the depths underneath stay readable
+fn default_layer_config(layer_name: &str) -> LayerConfig {
+    let magenta = Some(ColorValue::Components(vec![197, 69, 195]));
+    let regulatory = |fill: &str, symbol: Option<&str>| LayerConfig {
+        color: magenta.clone(),
+        width: Some(1.5),
+        dash: Some(vec![8.0, 4.0]),
+        fill: Some(fill.to_string()),
+        boundary: Some(true),
+        symbol: symbol.map(|x| x.to_string()),
+        ..LayerConfig::default()
+    };
+    match layer_name {
+        "RESARE" => regulatory("none", Some("restrictions")),
+        "ACHARE" => regulatory("none", Some("anchorage")),
+        "CTNARE" | "PRCARE" => regulatory("none", Some("caution")),
+        "MIPARE" => regulatory("hatch", Some("caution")),
+        "DMPGRD" => regulatory("none", Some("information")),
+        "ISTZNE" | "CBLARE" | "PIPARE" => regulatory("none", None),
+        // traffic lanes are tinted lighter than the separation zones between them
+        "TSSLPT" => LayerConfig { color: magenta, fill: Some("solid".to_string()), opacity: Some(0.15), ..LayerConfig::default() },
+        "TSEZNE" => LayerConfig { color: magenta, fill: Some("solid".to_string()), opacity: Some(0.35), ..LayerConfig::default() },
+        "TSSBND" => LayerConfig { color: magenta, width: Some(2.0), dash: Some(vec![10.0, 5.0]), ..LayerConfig::default() },
+        // fairways are only outlined, what matters is the depth inside them
+        "FAIRWY" => LayerConfig {
+            color: Some(ColorValue::Components(vec![125, 137, 140])),
+            width: Some(1.0),
+            dash: Some(vec![6.0, 4.0]),
+            fill: Some("none".to_string()),
+            boundary: Some(true),
+            ..LayerConfig::default()
+        },
+        _ => LayerConfig::default(),
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GpsConfig {
+    // serial:/dev/ttyUSB0, tcp:host:port, udp:port, file:path or sim:lon,lat,course,knots
+    pub source: String,
+    // how many times faster than real time logs and the simulator run
+    #[serde(default = "default_gps_speed")]
+    pub speed: f64,
+}
+
+fn default_gps_speed() -> f64 {
+    1.0
+}
+
+impl GpsConfig {
+    pub fn gps_source(&self) -> Option<GpsSource> {
+        GpsSource::from_spec(&self.source)
+    }
+}
+
+/// Depths in meters that decide how the water is shaded. Safety depth and the safety contour
+/// follow the draft unless they're set, the way most people want them.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SafetyConfig {
+    pub draft: f64,
+    pub under_keel_clearance: f64,
+    // soundings shallower than this are emphasized
+    pub safety_depth: Option<f64>,
+    pub shallow_contour: f64,
+    // the highlighted line between water we can and can't sail in
+    pub safety_contour: Option<f64>,
+    pub deep_contour: f64,
+    // only shade water as safe or unsafe instead of four shades
+    pub two_shades: bool,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> SafetyConfig {
+        SafetyConfig {
+            draft: 1.8,
+            under_keel_clearance: 1.0,
+            safety_depth: None,
+            shallow_contour: 2.0,
+            safety_contour: None,
+            deep_contour: 10.0,
+            two_shades: false,
+        }
+    }
+}
+
+impl SafetyConfig {
+    pub fn settings(&self) -> SafetySettings {
+        let safety_depth = self.safety_depth.unwrap_or(self.draft + self.under_keel_clearance);
+        SafetySettings {
+            draft: self.draft,
+            safety_depth,
+            shallow_contour: self.shallow_contour,
+            safety_contour: self.safety_contour.unwrap_or(safety_depth),
+            deep_contour: self.deep_contour,
+            two_shades: self.two_shades,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UnitsConfig {
+    // one of units::DEPTH_UNIT_NAMES, DISTANCE_UNIT_NAMES and SPEED_UNIT_NAMES
+    pub depth: String,
+    pub distance: String,
+    pub speed: String,
+}
+
+impl Default for UnitsConfig {
+    fn default() -> UnitsConfig {
+        UnitsConfig { depth: "feet".to_string(), distance: "nm".to_string(), speed: "kn".to_string() }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DisplayConfig {
+    // frames per second at most
+    pub frame_rate: u32,
+    // only draw a new frame when something changed instead of every frame
+    pub redraw_on_demand: bool,
+    // ttf/otf to write labels in, the built in OpenSans is used when unset or it won't load
+    pub font: Option<String>,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> DisplayConfig {
+        DisplayConfig { frame_rate: 30, redraw_on_demand: false, font: None }
+    }
+}
+
+/// colors can be given as [r, g, b], [r, g, b, a] or a name like "RED"
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ColorValue {
+    Components(Vec<i64>),
+    Name(String),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    UnknownLayer(String),
+    MissingLayerStyle(String),
+    BadColorArity(String, usize),
+    BadColorComponent(String, i64),
+    UnknownColorName(String, String),
+    MissingChartDirectory(String),
+    BadLineWidth(String, f32),
+    BadDash(String),
+    UnknownLineCap(String, String),
+    UnknownFill(String, String),
+    BadOpacity(String, f32),
+    BadHatchSpacing(String, f32),
+    UnknownAreaSymbol(String, String),
+    UnknownProjection(String),
+    UnknownIalaRegion(String),
+    BadGpsSource(String),
+    BadGpsSpeed(f64),
+    BadDraft(f64),
+    BadContours(f64, f64, f64),
+    UnknownUnit(&'static str, String, [&'static str; 3]),
+    BadFrameRate,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "couldn't read {path}: {e}"),
+            ConfigError::Parse(path, e) => write!(f, "couldn't parse {path}: {e}"),
+            ConfigError::UnknownLayer(layer) => write!(f, "unknown layer name '{layer}', expected one of {:?}", KNOWN_LAYERS),
+            ConfigError::MissingLayerStyle(layer) => write!(f, "layer '{layer}' is enabled but has no [{layer}] table with a color"),
+            ConfigError::BadColorArity(layer, n) => write!(f, "[{layer}] color has {n} values, expected 3 or 4"),
+            ConfigError::BadColorComponent(layer, v) => write!(f, "[{layer}] color value {v} is outside 0-255"),
+            ConfigError::UnknownColorName(layer, name) => write!(f, "[{layer}] unknown color name '{name}'"),
+            ConfigError::MissingChartDirectory(dir) => write!(f, "chart directory '{dir}' does not exist"),
+            ConfigError::BadLineWidth(layer, w) => write!(f, "[{layer}] width {w} must be greater than 0"),
+            ConfigError::BadDash(layer) => write!(f, "[{layer}] dash lengths must not be negative"),
+            ConfigError::UnknownLineCap(layer, cap) => write!(f, "[{layer}] unknown cap '{cap}', expected butt, square or round"),
+            ConfigError::UnknownFill(layer, fill) => write!(f, "[{layer}] unknown fill '{fill}', expected one of {:?}", AREA_FILL_NAMES),
+            ConfigError::BadOpacity(layer, opacity) => write!(f, "[{layer}] opacity {opacity} must be between 0 and 1"),
+            ConfigError::BadHatchSpacing(layer, spacing) => write!(f, "[{layer}] hatch_spacing {spacing} must be greater than 0"),
+            ConfigError::UnknownAreaSymbol(layer, symbol) => write!(f, "[{layer}] unknown symbol '{symbol}', expected one of {:?}", AREA_SYMBOL_NAMES),
+            ConfigError::UnknownProjection(name) => write!(f, "unknown projection '{name}', expected one of {:?}", PROJECTION_NAMES),
+            ConfigError::UnknownIalaRegion(name) => write!(f, "unknown iala_region '{name}', expected one of {:?}", IALA_REGION_NAMES),
+            ConfigError::BadGpsSource(source) => write!(f, "[gps] bad source '{source}', expected serial:DEVICE, tcp:HOST:PORT, udp:PORT, file:PATH or sim:LON,LAT,COURSE,KNOTS"),
+            ConfigError::BadGpsSpeed(speed) => write!(f, "[gps] speed {speed} must be greater than 0"),
+            ConfigError::BadDraft(draft) => write!(f, "[safety] draft {draft} must be greater than 0"),
+            ConfigError::UnknownUnit(kind, name, names) => write!(f, "[units] unknown {kind} unit '{name}', expected one of {:?}", names),
+            ConfigError::BadFrameRate => write!(f, "[display] frame_rate must be greater than 0"),
+            ConfigError::BadContours(shallow, safety, deep) => {
+                write!(f, "[safety] contours must go shallow <= safety <= deep, got {shallow}, {safety}, {deep}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            resolution_1: 1920,
+            resolution_2: 1080,
+            chart_directory: "./src/charts/".to_string(),
+            layers: Vec::new(),
+            projection: "mercator".to_string(),
+            iala_region: "A".to_string(),
+            gps: None,
+            safety: SafetyConfig::default(),
+            units: UnitsConfig::default(),
+            display: DisplayConfig::default(),
+            layer_styles: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_string(), e))?;
+        Config::from_toml(&contents).map_err(|e| ConfigError::Parse(path.to_string(), e))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        // layer tables are flattened in, so serde would report a misspelt setting as a layer table that isn't one
+        let table: toml::Table = toml::from_str(contents)?;
+        if let Some(key) = table.iter().find(|(key, value)| !value.is_table() && !SETTING_NAMES.contains(&key.as_str())).map(|x| x.0) {
+            return Err(serde::de::Error::custom(format!("unknown setting '{key}', expected one of {:?} or a [LAYER] table", SETTING_NAMES)));
+        }
+        toml::Value::Table(table).try_into()
+    }
+
+    /// returns every problem with the config instead of stopping at the first one
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        if !Path::new(&self.chart_directory).is_dir() {
+            errors.push(ConfigError::MissingChartDirectory(self.chart_directory.clone()));
+        }
+        if projection_from_name(&self.projection).is_none() {
+            errors.push(ConfigError::UnknownProjection(self.projection.clone()));
+        }
+        if IalaRegion::from_name(&self.iala_region).is_none() {
+            errors.push(ConfigError::UnknownIalaRegion(self.iala_region.clone()));
+        }
+        if let Some(gps) = &self.gps {
+            if gps.gps_source().is_none() {
+                errors.push(ConfigError::BadGpsSource(gps.source.clone()));
+            }
+            if gps.speed <= 0.0 {
+                errors.push(ConfigError::BadGpsSpeed(gps.speed));
+            }
+        }
+        let safety = self.safety.settings();
+        if safety.draft <= 0.0 {
+            errors.push(ConfigError::BadDraft(safety.draft));
+        }
+        if !(safety.shallow_contour <= safety.safety_contour && safety.safety_contour <= safety.deep_contour) {
+            errors.push(ConfigError::BadContours(safety.shallow_contour, safety.safety_contour, safety.deep_contour));
+        }
+        if DepthUnit::from_name(&self.units.depth).is_none() {
+            errors.push(ConfigError::UnknownUnit("depth", self.units.depth.clone(), DEPTH_UNIT_NAMES));
+        }
+        if DistanceUnit::from_name(&self.units.distance).is_none() {
+            errors.push(ConfigError::UnknownUnit("distance", self.units.distance.clone(), DISTANCE_UNIT_NAMES));
+        }
+        if SpeedUnit::from_name(&self.units.speed).is_none() {
+            errors.push(ConfigError::UnknownUnit("speed", self.units.speed.clone(), SPEED_UNIT_NAMES));
+        }
+        if self.display.frame_rate == 0 {
+            errors.push(ConfigError::BadFrameRate);
+        }
+        for layer in &self.layers {
+            if !KNOWN_LAYERS.contains(&&layer[..]) {
+                errors.push(ConfigError::UnknownLayer(layer.clone()));
+            }
+            if self.layer_config(layer).color.is_none() && !ROUTE_LAYERS.contains(&&layer[..]) {
+                errors.push(ConfigError::MissingLayerStyle(layer.clone()));
+            }
+        }
+        for (layer, style) in &self.layer_styles {
+            if !KNOWN_LAYERS.contains(&&layer[..]) {
+                errors.push(ConfigError::UnknownLayer(layer.clone()));
+            }
+            if let Some(color) = &style.color {
+                if let Err(e) = parse_color(layer, color) {
+                    errors.push(e);
                 }
-                else if a.len() == 4  {
-                    let a = a[3].as_integer().unwrap() as u8;
-                    Color::rgba(r, g, b, a)
+            }
+            if let Some(width) = style.width {
+                if width <= 0.0 {
+                    errors.push(ConfigError::BadLineWidth(layer.clone(), width));
                 }
-                else {
-                    println!("Wrong number of color values: {} expected 3 or 4", a.len());
-                    println!("Defaulting to RED");
-                    Color::RED
+            }
+            if style.dash.iter().flatten().any(|x| *x < 0.0) {
+                errors.push(ConfigError::BadDash(layer.clone()));
+            }
+            if let Some(cap) = &style.cap {
+                if LineCap::from_name(cap).is_none() {
+                    errors.push(ConfigError::UnknownLineCap(layer.clone(), cap.clone()));
                 }
-            },
-            toml::Value::String(s) => {
-                match s.to_uppercase().as_str() {
-                    "RED" => Color::RED,
-                    "GREEN" => Color::GREEN,
-                    "BLUE" => Color::BLUE,
-                    "YELLOW" => Color::YELLOW,
-                    "MAGENTA" => Color::MAGENTA,
-                    "CYAN" => Color::CYAN,
-                    "TRANSPARENT" => Color::TRANSPARENT,
-                    _ => {
-                        println!("Unknown color: {}", s);
-                        println!("Defaulting to RED");
-                        Color::RED
-                    
-                    },
+            }
+            if let Some(fill) = &style.fill {
+                if AreaFill::from_name(fill).is_none() {
+                    errors.push(ConfigError::UnknownFill(layer.clone(), fill.clone()));
                 }
-            },
-            _ => {
-                println!("Unknown color: {:?}", v);
-                println!("Defaulting to RED");
+            }
+            if let Some(opacity) = style.opacity {
+                if !(0.0..=1.0).contains(&opacity) {
+                    errors.push(ConfigError::BadOpacity(layer.clone(), opacity));
+                }
+            }
+            if let Some(spacing) = style.hatch_spacing {
+                if spacing <= 0.0 {
+                    errors.push(ConfigError::BadHatchSpacing(layer.clone(), spacing));
+                }
+            }
+            if let Some(symbol) = &style.symbol {
+                if AreaSymbolRule::from_name(symbol).is_none() {
+                    errors.push(ConfigError::UnknownAreaSymbol(layer.clone(), symbol.clone()));
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        (self.resolution_1, self.resolution_2)
+    }
+
+    pub fn projection(&self) -> Box<dyn Projection> {
+        projection_from_name(&self.projection).unwrap_or_else(|| {
+            log::warn!("Unknown projection {}, defaulting to mercator", self.projection);
+            Box::new(SphericalMercator::default())
+        })
+    }
+
+    pub fn iala_region(&self) -> IalaRegion {
+        IalaRegion::from_name(&self.iala_region).unwrap_or(IalaRegion::A)
+    }
+
+    /// units to show things in, anything unknown falls back to the defaults
+    pub fn units(&self) -> Units {
+        let default = Units::default();
+        Units {
+            depth: DepthUnit::from_name(&self.units.depth).unwrap_or(default.depth),
+            distance: DistanceUnit::from_name(&self.units.distance).unwrap_or(default.distance),
+            speed: SpeedUnit::from_name(&self.units.speed).unwrap_or(default.speed),
+        }
+    }
+
+    /// the [LAYER] table with anything it leaves out filled in from the built in defaults
+    pub fn layer_config(&self, layer_name: &str) -> LayerConfig {
+        let default = default_layer_config(layer_name);
+        match self.layer_styles.get(layer_name) {
+            Some(style) => style.or(default),
+            None => default,
+        }
+    }
+
+    pub fn color_for_layer(&self, layer_name: &str) -> Color {
+        let style = self.layer_config(layer_name);
+        match style.color.as_ref().map(|c| parse_color(layer_name, c)) {
+            Some(Ok(color)) => color,
+            Some(Err(e)) => {
+                log::warn!("{e}, defaulting to RED");
+                Color::RED
+            }
+            None => {
+                log::warn!("No color set for {layer_name}, defaulting to RED");
                 Color::RED
-            },
+            }
         }
-    };
-    color
+    }
+
+    pub fn line_style_for_layer(&self, layer_name: &str) -> LineStyle {
+        let color = self.color_for_layer(layer_name);
+        let style = self.layer_config(layer_name);
+        LineStyle {
+            color,
+            width: style.width.unwrap_or(DEFAULT_LINE_WIDTH),
+            dash: style.dash.clone().unwrap_or_default(),
+            cap: style.cap.as_deref().and_then(LineCap::from_name).unwrap_or(LineCap::Butt),
+        }
+    }
+
+    pub fn area_style_for_layer(&self, layer_name: &str) -> AreaStyle {
+        let style = self.layer_config(layer_name);
+        let default = AreaStyle::default();
+        AreaStyle {
+            fill: style.fill.as_deref().and_then(AreaFill::from_name).unwrap_or(default.fill),
+            opacity: style.opacity.map_or(default.opacity, |x| x.clamp(0.0, 1.0)),
+            boundary: style.boundary.unwrap_or(default.boundary),
+            hatch_spacing: style.hatch_spacing.filter(|x| *x > 0.0).unwrap_or(default.hatch_spacing),
+            hatch_angle: style.hatch_angle.unwrap_or(default.hatch_angle),
+            symbol: style.symbol.as_deref().and_then(AreaSymbolRule::from_name),
+        }
+    }
 }
 
-pub fn get_chart_directory() -> String {
-    let c = get_config();
-    c["chart_directory"].as_str().unwrap().to_string()
-}
\ No newline at end of file
+pub fn parse_color(layer_name: &str, value: &ColorValue) -> Result<Color, ConfigError> {
+    match value {
+        ColorValue::Components(a) => {
+            if a.len() != 3 && a.len() != 4 {
+                return Err(ConfigError::BadColorArity(layer_name.to_string(), a.len()));
+            }
+            let mut c = [255u8; 4];
+            for (i, v) in a.iter().enumerate() {
+                c[i] = u8::try_from(*v).map_err(|_| ConfigError::BadColorComponent(layer_name.to_string(), *v))?;
+            }
+            Ok(Color::rgba(c[0], c[1], c[2], c[3]))
+        }
+        ColorValue::Name(s) => {
+            match s.to_uppercase().as_str() {
+                "RED" => Ok(Color::RED),
+                "GREEN" => Ok(Color::GREEN),
+                "BLUE" => Ok(Color::BLUE),
+                "YELLOW" => Ok(Color::YELLOW),
+                "MAGENTA" => Ok(Color::MAGENTA),
+                "CYAN" => Ok(Color::CYAN),
+                "WHITE" => Ok(Color::WHITE),
+                "BLACK" => Ok(Color::BLACK),
+                "TRANSPARENT" => Ok(Color::TRANSPARENT),
+                _ => Err(ConfigError::UnknownColorName(layer_name.to_string(), s.clone())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every check in validate passes with this, the tests add one thing wrong at a time
+    const VALID: &str = "chart_directory = \".\"\nlayers = ['LNDARE']\n[LNDARE]\ncolor = [201, 185, 122]\n";
+
+    fn errors(extra: &str) -> Vec<String> {
+        let config = Config::from_toml(&format!("{VALID}{extra}")).unwrap();
+        config.validate().iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn bundled_config() {
+        let config = Config::from_toml(include_str!("../config.toml")).unwrap();
+        assert!(config.layers.contains(&"LNDARE".to_string()));
+        assert!(config.layer_styles.contains_key("FAIRWY"));
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(errors(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_setting() {
+        let error = Config::from_toml("resolution = 1920\n").unwrap_err().to_string();
+        assert!(error.contains("unknown setting 'resolution'"), "{error}");
+        // a table with a setting's name is still read as that setting
+        assert_eq!(Config::from_toml("[safety]\ndraft = 2.5\n").unwrap().safety.draft, 2.5);
+    }
+
+    #[test]
+    fn unknown_layer_table() {
+        assert_eq!(errors("[LNDAR]\ncolor = [1, 2, 3]\n"), vec![format!("unknown layer name 'LNDAR', expected one of {:?}", KNOWN_LAYERS)]);
+    }
+
+    #[test]
+    fn unknown_layer_field() {
+        let error = Config::from_toml("[LNDARE]\ncolour = [1, 2, 3]\n").unwrap_err().to_string();
+        assert!(error.contains("unknown field `colour`"), "{error}");
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("LNDARE", &ColorValue::Components(vec![1, 2, 3])).unwrap(), Color::rgba(1, 2, 3, 255));
+        assert_eq!(parse_color("LNDARE", &ColorValue::Components(vec![1, 2, 3, 4])).unwrap(), Color::rgba(1, 2, 3, 4));
+        assert_eq!(parse_color("LNDARE", &ColorValue::Name("magenta".to_string())).unwrap(), Color::MAGENTA);
+        assert!(matches!(parse_color("LNDARE", &ColorValue::Name("puce".to_string())), Err(ConfigError::UnknownColorName(_, _))));
+        assert!(matches!(parse_color("LNDARE", &ColorValue::Components(vec![1, 2])), Err(ConfigError::BadColorArity(_, 2))));
+        assert!(matches!(parse_color("LNDARE", &ColorValue::Components(vec![1, 2, 256])), Err(ConfigError::BadColorComponent(_, 256))));
+        assert!(matches!(parse_color("LNDARE", &ColorValue::Components(vec![-1, 2, 3])), Err(ConfigError::BadColorComponent(_, -1))));
+        assert_eq!(errors("[COALNE]\ncolor = \"puce\"\n"), vec!["[COALNE] unknown color name 'puce'".to_string()]);
+    }
+
+    #[test]
+    fn validate_errors() {
+        let config = Config::from_toml(
+            "chart_directory = \"no/such/directory\"\n\
+             projection = \"lambert\"\n\
+             iala_region = \"C\"\n\
+             layers = ['COALNE', 'LNDAR']\n\
+             [gps]\nsource = \"bluetooth:boat\"\nspeed = 0.0\n\
+             [safety]\ndraft = 0.0\nshallow_contour = 5.0\ndeep_contour = 3.0\n\
+             [units]\ndepth = \"cubits\"\ndistance = \"leagues\"\nspeed = \"mph\"\n\
+             [display]\nframe_rate = 0\n\
+             [DEPCNT]\ncolor = [0, 0, 0]\nwidth = 0.0\ndash = [4.0, -1.0]\ncap = \"pointy\"\n\
+             [RESARE]\nfill = \"dots\"\nopacity = 1.5\nhatch_spacing = 0.0\nsymbol = \"skull\"\n",
+        )
+        .unwrap();
+        let errors = config.validate();
+        let has = |f: fn(&ConfigError) -> bool| errors.iter().any(f);
+        assert!(has(|x| matches!(x, ConfigError::MissingChartDirectory(_))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownProjection(_))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownIalaRegion(_))));
+        assert!(has(|x| matches!(x, ConfigError::BadGpsSource(_))));
+        assert!(has(|x| matches!(x, ConfigError::BadGpsSpeed(_))));
+        assert!(has(|x| matches!(x, ConfigError::BadDraft(_))));
+        assert!(has(|x| matches!(x, ConfigError::BadContours(_, _, _))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownUnit("depth", _, _))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownUnit("distance", _, _))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownUnit("speed", _, _))));
+        assert!(has(|x| matches!(x, ConfigError::BadFrameRate)));
+        assert!(has(|x| matches!(x, ConfigError::UnknownLayer(layer) if layer == "LNDAR")));
+        assert!(has(|x| matches!(x, ConfigError::MissingLayerStyle(layer) if layer == "COALNE")));
+        assert!(has(|x| matches!(x, ConfigError::BadLineWidth(_, _))));
+        assert!(has(|x| matches!(x, ConfigError::BadDash(_))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownLineCap(_, _))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownFill(_, _))));
+        assert!(has(|x| matches!(x, ConfigError::BadOpacity(_, _))));
+        assert!(has(|x| matches!(x, ConfigError::BadHatchSpacing(_, _))));
+        assert!(has(|x| matches!(x, ConfigError::UnknownAreaSymbol(_, _))));
+        assert_eq!(errors.len(), 21, "{errors:?}");
+    }
+}
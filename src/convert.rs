@@ -0,0 +1,145 @@
+#![allow(non_upper_case_globals)]
+use std::collections::HashMap;
+use std::fmt;
+
+use gdal::vector::{Geometry, OGRwkbGeometryType::{self, *}};
+use geo::{Coord, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon};
+use log::{info, warn};
+
+const WKB_25D_BIT: OGRwkbGeometryType::Type = 0x8000_0000;
+
+/// strips the 25D bit and the ISO Z/M/ZM offsets so wkbPolygon25D, wkbPolygonZM, etc are all wkbPolygon
+pub fn flatten_geometry_type(geometry_type: OGRwkbGeometryType::Type) -> OGRwkbGeometryType::Type {
+    (geometry_type & !WKB_25D_BIT) % 1000
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvertError {
+    Empty,
+    Unsupported(String),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Empty => write!(f, "empty geometry"),
+            ConvertError::Unsupported(name) => write!(f, "unsupported geometry type {name}"),
+        }
+    }
+}
+
+fn coords_from_geometry(geometry: &Geometry) -> Vec<Coord> {
+    geometry.get_point_vec().iter().map(|x| Coord { x: x.0, y: x.1 }).collect()
+}
+
+fn line_string_from_geometry(geometry: &Geometry) -> LineString {
+    LineString::new(coords_from_geometry(geometry))
+}
+
+fn polygon_from_geometry(geometry: &Geometry) -> Result<Polygon, ConvertError> {
+    let ring_count = geometry.geometry_count();
+    if ring_count == 0 {
+        return Err(ConvertError::Empty);
+    }
+    // the first ring is the exterior and the rest are holes
+    let exterior = line_string_from_geometry(&geometry.get_geometry(0));
+    let interiors = (1..ring_count).map(|i| line_string_from_geometry(&geometry.get_geometry(i))).collect();
+    Ok(Polygon::new(exterior, interiors))
+}
+
+fn sub_geometries<T>(geometry: &Geometry, convert: impl Fn(&Geometry) -> Result<T, ConvertError>) -> Result<Vec<T>, ConvertError> {
+    let mut items = Vec::new();
+    for i in 0..geometry.geometry_count() {
+        match convert(&geometry.get_geometry(i)) {
+            Ok(item) => items.push(item),
+            // an empty part shouldn't throw away the rest of the feature
+            Err(ConvertError::Empty) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(items)
+}
+
+/// converts any OGR geometry, including the 25D/M/ZM flavours, into a 2D `geo` geometry
+pub fn geo_geometry_from_gdal(geometry: &Geometry) -> Result<geo::Geometry, ConvertError> {
+    if geometry.is_empty() {
+        return Err(ConvertError::Empty);
+    }
+    let converted = match flatten_geometry_type(geometry.geometry_type()) {
+        wkbPoint => {
+            let (x, y, _) = geometry.get_point(0);
+            geo::Geometry::Point(Point::new(x, y))
+        }
+        wkbMultiPoint => {
+            let points = sub_geometries(geometry, |g| Ok(Point::new(g.get_point(0).0, g.get_point(0).1)))?;
+            geo::Geometry::MultiPoint(MultiPoint::new(points))
+        }
+        wkbLineString | wkbLinearRing => geo::Geometry::LineString(line_string_from_geometry(geometry)),
+        wkbMultiLineString => {
+            let lines = sub_geometries(geometry, |g| Ok(line_string_from_geometry(g)))?;
+            geo::Geometry::MultiLineString(MultiLineString::new(lines))
+        }
+        wkbPolygon => geo::Geometry::Polygon(polygon_from_geometry(geometry)?),
+        wkbMultiPolygon => {
+            let polygons = sub_geometries(geometry, polygon_from_geometry)?;
+            geo::Geometry::MultiPolygon(MultiPolygon::new(polygons))
+        }
+        wkbGeometryCollection => {
+            let geometries = sub_geometries(geometry, geo_geometry_from_gdal)?;
+            geo::Geometry::GeometryCollection(GeometryCollection::new_from(geometries))
+        }
+        _ => return Err(ConvertError::Unsupported(geometry.geometry_name())),
+    };
+    Ok(converted)
+}
+
+/// keeps track of what happened to every feature in a layer so nothing gets dropped quietly
+#[derive(Debug, Default, Clone)]
+pub struct ConversionStats {
+    pub features: usize,
+    pub converted: usize,
+    pub no_geometry: usize,
+    pub empty: usize,
+    // features that converted fine but that the loader had no use for, e.g. points in an area layer
+    pub skipped: usize,
+    pub unsupported: HashMap<String, usize>,
+}
+
+impl ConversionStats {
+    /// converts a feature's geometry and records the outcome
+    pub fn convert(&mut self, geometry: Option<&Geometry>) -> Option<geo::Geometry> {
+        self.features += 1;
+        let geometry = match geometry {
+            Some(geometry) => geometry,
+            None => {
+                self.no_geometry += 1;
+                return None;
+            }
+        };
+        match geo_geometry_from_gdal(geometry) {
+            Ok(converted) => {
+                self.converted += 1;
+                Some(converted)
+            }
+            Err(ConvertError::Empty) => {
+                self.empty += 1;
+                None
+            }
+            Err(ConvertError::Unsupported(name)) => {
+                *self.unsupported.entry(name).or_insert(0) += 1;
+                None
+            }
+        }
+    }
+
+    pub fn unsupported_count(&self) -> usize {
+        self.unsupported.values().sum()
+    }
+
+    pub fn report(&self, layer_name: &str) {
+        info!("{layer_name}: converted {} of {} features ({} without geometry, {} empty, {} skipped)", self.converted, self.features, self.no_geometry, self.empty, self.skipped);
+        if !self.unsupported.is_empty() {
+            warn!("{layer_name}: dropped {} features with unsupported geometry {:?}", self.unsupported_count(), self.unsupported);
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{HashMap, HashSet};
+
+use gdal::Dataset;
+use gdal::vector::LayerAccess;
+use geo::{Area, BoundingRect, Contains, Coord, Line, LineString, Point, Polygon};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+use sfml::graphics::Color;
+use sfml::system::Vector2f;
+
+use crate::attributes::Attributes;
+use crate::convert::ConversionStats;
+use crate::geometry::{get_layers, lines_from_geo_geometry, polygons_from_geo_geometry, project_geometry, rings, DepthLayer, LayerExtent, DEPARE};
+use crate::hazards::Hazard;
+use crate::transform::Projection;
+
+/// One DEPARE polygon and the depth range inside it, in world coordinates
+pub struct DepthArea {
+    pub polygon: Polygon,
+    // DRVAL1, DRVAL2 in meters
+    pub depth: (f64, f64),
+    area: f64,
+}
+
+/// Answers "how deep is it here?" from the DEPARE polygons, SOUNDG soundings and the wrecks, obstructions and rocks of every chart.
+/// Bounding boxes go in an R-tree so a query only tests the handful of polygons that could contain the point.
+pub struct DepthIndex {
+    areas: Vec<DepthArea>,
+    area_tree: RTree<GeomWithData<Rectangle<[f64; 2]>, usize>>,
+    // depth in meters
+    sounding_tree: RTree<GeomWithData<[f64; 2], f64>>,
+    hazards: Vec<Hazard>,
+    // bounding box of each hazard with its index in `hazards`
+    hazard_tree: RTree<GeomWithData<Rectangle<[f64; 2]>, usize>>,
+}
+
+impl DepthIndex {
+    /// works out which of the hazards are isolated dangers at these safety settings
+    pub fn new(depare_map: &HashMap<u16, Vec<DEPARE>>, soundings: &[DepthLayer], mut hazards: Vec<Hazard>, safety: &SafetySettings) -> DepthIndex {
+        let mut areas = Vec::new();
+        let mut boxes = Vec::new();
+        for depare in depare_map.values().flatten() {
+            for layer in &depare.layers {
+                let rect = match layer.polygon.bounding_rect() {
+                    Some(rect) => rect,
+                    None => continue,
+                };
+                boxes.push(GeomWithData::new(Rectangle::from_corners(rect.min().into(), rect.max().into()), areas.len()));
+                areas.push(DepthArea { polygon: layer.polygon.clone(), depth: layer.depth, area: layer.polygon.unsigned_area() });
+            }
+        }
+        let points = soundings.iter().flat_map(|layer| layer.coordinates.iter()).map(|(x, y, depth)| GeomWithData::new([*x, *y], *depth)).collect();
+        let mut index = DepthIndex { areas, area_tree: RTree::bulk_load(boxes), sounding_tree: RTree::bulk_load(points), hazards: Vec::new(), hazard_tree: RTree::new() };
+
+        let mut hazard_boxes = Vec::new();
+        for (i, hazard) in hazards.iter_mut().enumerate() {
+            let p = hazard.position;
+            let surrounding = index.depth_at((p.x as f64, p.y as f64)).map(|x| x.0);
+            hazard.isolated_danger = hazard.is_isolated_danger(surrounding, safety);
+            let (mut min, mut max) = ([p.x as f64, p.y as f64], [p.x as f64, p.y as f64]);
+            for point in hazard.outline.iter().flatten() {
+                min = [min[0].min(point.x as f64), min[1].min(point.y as f64)];
+                max = [max[0].max(point.x as f64), max[1].max(point.y as f64)];
+            }
+            hazard_boxes.push(GeomWithData::new(Rectangle::from_corners(min, max), i));
+        }
+        index.hazards = hazards;
+        index.hazard_tree = RTree::bulk_load(hazard_boxes);
+        index
+    }
+
+    /// the depth area at a world position, where charts overlap the smallest (most detailed) area wins
+    pub fn area_at(&self, point: (f64, f64)) -> Option<&DepthArea> {
+        let geo_point = Point::new(point.0, point.1);
+        self.area_tree
+            .locate_all_at_point(&[point.0, point.1])
+            .map(|x| &self.areas[x.data])
+            .filter(|x| x.polygon.contains(&geo_point))
+            .min_by(|a, b| a.area.total_cmp(&b.area))
+    }
+
+    /// DRVAL1, DRVAL2 in meters at a world position
+    pub fn depth_at(&self, point: (f64, f64)) -> Option<(f64, f64)> {
+        self.area_at(point).map(|x| x.depth)
+    }
+
+    /// world position and depth in meters of the closest sounding
+    pub fn nearest_sounding(&self, point: (f64, f64)) -> Option<((f64, f64), f64)> {
+        self.sounding_tree.nearest_neighbor(&[point.0, point.1]).map(|x| ((x.geom()[0], x.geom()[1]), x.data))
+    }
+
+    /// every sounding inside an extent as world x, y and depth in meters
+    pub fn soundings_in(&self, extent: &LayerExtent) -> Vec<(f64, f64, f64)> {
+        self.sounding_tree.locate_in_envelope(&envelope(extent)).map(|x| (x.geom()[0], x.geom()[1], x.data)).collect()
+    }
+
+    /// the hazard closest to a world position, or the one it's inside for areas
+    pub fn nearest_hazard(&self, point: (f64, f64)) -> Option<&Hazard> {
+        self.hazard_tree.nearest_neighbor(&[point.0, point.1]).map(|x| &self.hazards[x.data])
+    }
+
+    /// every hazard with some part inside an extent
+    pub fn hazards_in(&self, extent: &LayerExtent) -> Vec<&Hazard> {
+        self.hazard_tree.locate_in_envelope_intersecting(&envelope(extent)).map(|x| &self.hazards[x.data]).collect()
+    }
+}
+
+fn envelope(extent: &LayerExtent) -> AABB<[f64; 2]> {
+    AABB::from_corners([extent.MinX as f64, extent.MinY as f64], [extent.MaxX as f64, extent.MaxY as f64])
+}
+
+// S-52 day palette depth shades
+pub const DEPIT: Color = Color::rgb(131, 178, 149);
+pub const DEPVS: Color = Color::rgb(115, 182, 239);
+pub const DEPMS: Color = Color::rgb(152, 197, 242);
+pub const DEPMD: Color = Color::rgb(186, 213, 225);
+pub const DEPDW: Color = Color::rgb(212, 234, 238);
+// safety contour
+pub const DEPSC: Color = Color::rgb(82, 90, 92);
+// soundings deeper and shallower than the safety depth
+pub const SNDG1: Color = Color::rgb(125, 137, 140);
+pub const SNDG2: Color = Color::rgb(0, 0, 0);
+
+/// Safety depths worked out from the [safety] config, all in meters
+#[derive(Clone, Debug, PartialEq)]
+pub struct SafetySettings {
+    pub draft: f64,
+    pub safety_depth: f64,
+    pub shallow_contour: f64,
+    pub safety_contour: f64,
+    pub deep_contour: f64,
+    pub two_shades: bool,
+}
+
+impl SafetySettings {
+    /// shade for a DEPARE going by its shallowest depth, DRVAL1
+    pub fn color_for_depth(&self, drval1: f64) -> Color {
+        if drval1 < 0.0 {
+            return DEPIT;
+        }
+        if self.two_shades {
+            return if self.is_safe(drval1) { DEPDW } else { DEPVS };
+        }
+        if drval1 >= self.deep_contour {
+            DEPDW
+        }
+        else if drval1 >= self.safety_contour {
+            DEPMD
+        }
+        else if drval1 >= self.shallow_contour {
+            DEPMS
+        }
+        else {
+            DEPVS
+        }
+    }
+
+    /// whether a depth area is on the deep side of the safety contour
+    pub fn is_safe(&self, drval1: f64) -> bool {
+        drval1 >= self.safety_contour
+    }
+}
+
+type EdgeKey = ((u64, u64), (u64, u64));
+
+fn edge_key(a: Coord, b: Coord) -> EdgeKey {
+    let a = (a.x.to_bits(), a.y.to_bits());
+    let b = (b.x.to_bits(), b.y.to_bits());
+    if a < b { (a, b) } else { (b, a) }
+}
+
+// layers the safety contour is worked out from besides DEPARE
+pub const CONTOUR_LAYERS: [&str; 3] = ["DEPCNT", "LNDARE", "DRGARE"];
+
+// DEPCNT VALDCO is given to the centimeter
+const CONTOUR_TOLERANCE: f64 = 0.01;
+// world meters past the edge of a cell to look for another chart's depth area
+const EDGE_PROBE_DISTANCE: f64 = 1.0;
+
+/// What the safety contour needs out of a cell besides DEPARE
+#[derive(Default)]
+pub struct ContourFeatures {
+    // land and dredged areas shallower than the safety contour, safe water that meets them is on the contour
+    pub shallow_areas: Vec<Polygon>,
+    // dredged areas at least as deep as the safety contour
+    pub safe_areas: Vec<Polygon>,
+    // DEPCNT lines with their VALDCO in meters
+    pub contours: Vec<(LineString, f64)>,
+}
+
+impl ContourFeatures {
+    pub fn extend(&mut self, other: ContourFeatures) {
+        self.shallow_areas.extend(other.shallow_areas);
+        self.safe_areas.extend(other.safe_areas);
+        self.contours.extend(other.contours);
+    }
+}
+
+/// reads the depth contours, land and dredged areas of a dataset
+pub fn get_contour_features(ds: &Dataset, projection: &dyn Projection, safety: &SafetySettings) -> ContourFeatures {
+    let mut features = ContourFeatures::default();
+    for mut layer in get_layers(ds, CONTOUR_LAYERS.to_vec()) {
+        let layer_name = layer.name();
+        let mut stats = ConversionStats::default();
+        for feature in layer.features() {
+            let geometry = match stats.convert(feature.geometry()) {
+                Some(geometry) => project_geometry(geometry, projection),
+                None => continue,
+            };
+            let attributes = Attributes::new(&feature);
+            match layer_name.as_str() {
+                "DEPCNT" => match attributes.float("VALDCO") {
+                    Some(depth) => features.contours.extend(lines_from_geo_geometry(geometry).into_iter().map(|x| (x, depth))),
+                    None => stats.skipped += 1,
+                },
+                "LNDARE" => features.shallow_areas.extend(polygons_from_geo_geometry(geometry)),
+                // a dredged area without a depth isn't trusted to be safe
+                _ => match attributes.float("DRVAL1") {
+                    Some(depth) if safety.is_safe(depth) => features.safe_areas.extend(polygons_from_geo_geometry(geometry)),
+                    _ => features.shallow_areas.extend(polygons_from_geo_geometry(geometry)),
+                },
+            }
+        }
+        stats.report(&layer_name);
+    }
+    features
+}
+
+/// whether the water just past an edge of `polygon` is shallower than the safety contour in another chart
+fn shallow_beyond(line: Line, polygon: &Polygon, index: &DepthIndex, safety: &SafetySettings) -> bool {
+    let d = line.delta();
+    let length = (d.x * d.x + d.y * d.y).sqrt();
+    if length == 0.0 {
+        return false;
+    }
+    let middle = (line.start + line.end) / 2.0;
+    let normal = Coord { x: -d.y, y: d.x } * (EDGE_PROBE_DISTANCE / length);
+    let outside = [middle + normal, middle - normal].into_iter().find(|x| !polygon.contains(&Point::from(*x)));
+    outside.and_then(|x| index.depth_at((x.x, x.y))).is_some_and(|x| !safety.is_safe(x.0))
+}
+
+/// splits a line into the runs of segments `keep` wants
+fn push_runs(line: &LineString, contours: &mut Vec<Vec<Vector2f>>, mut keep: impl FnMut(Line) -> bool) {
+    let mut current: Vec<Vector2f> = Vec::new();
+    for segment in line.lines() {
+        if !keep(segment) {
+            if current.len() > 1 {
+                contours.push(std::mem::take(&mut current));
+            }
+            current.clear();
+            continue;
+        }
+        if current.is_empty() {
+            current.push(Vector2f::new(segment.start.x as f32, segment.start.y as f32));
+        }
+        current.push(Vector2f::new(segment.end.x as f32, segment.end.y as f32));
+    }
+    if current.len() > 1 {
+        contours.push(current);
+    }
+}
+
+/// The safety contour is every edge of safe DEPARE water that meets shallower water or land. S-57 cells share edges
+/// between neighbouring areas so the coordinates line up exactly. An edge nothing else shares is the limit of the
+/// cell, and is only on the contour when another chart has shallow water past it. DEPCNT lines at the safety
+/// contour depth are added on top, they fill in where a cell's depth areas don't break at that depth
+pub fn safety_contour_lines(depare_map: &HashMap<u16, Vec<DEPARE>>, features: &ContourFeatures, index: &DepthIndex, safety: &SafetySettings) -> Vec<Vec<Vector2f>> {
+    let layers: Vec<_> = depare_map.values().flatten().flat_map(|depare| depare.layers.iter()).collect();
+    let shallow_areas = layers.iter().filter(|x| !safety.is_safe(x.depth.0)).map(|x| &x.polygon).chain(&features.shallow_areas);
+    let mut shallow_edges = HashSet::new();
+    for polygon in shallow_areas {
+        for ring in rings(polygon) {
+            shallow_edges.extend(ring.lines().map(|x| edge_key(x.start, x.end)));
+        }
+    }
+    // how many areas have each edge, the ones only one area has are the limit of a cell
+    let mut edge_counts: HashMap<EdgeKey, usize> = HashMap::new();
+    for polygon in layers.iter().map(|x| &x.polygon).chain(&features.shallow_areas).chain(&features.safe_areas) {
+        for ring in rings(polygon) {
+            for line in ring.lines() {
+                *edge_counts.entry(edge_key(line.start, line.end)).or_default() += 1;
+            }
+        }
+    }
+    let mut drawn = HashSet::new();
+    let mut contours = Vec::new();
+    for layer in layers.iter().filter(|x| safety.is_safe(x.depth.0)) {
+        for ring in rings(&layer.polygon) {
+            push_runs(ring, &mut contours, |line| {
+                let key = edge_key(line.start, line.end);
+                let cell_limit = edge_counts.get(&key) == Some(&1);
+                let on_contour = shallow_edges.contains(&key) || (cell_limit && shallow_beyond(line, &layer.polygon, index, safety));
+                on_contour && drawn.insert(key)
+            });
+        }
+    }
+    let depth_contours = features.contours.iter().filter(|(_, depth)| (depth - safety.safety_contour).abs() < CONTOUR_TOLERANCE);
+    for (line, _) in depth_contours {
+        push_runs(line, &mut contours, |x| drawn.insert(edge_key(x.start, x.end)));
+    }
+    contours
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{coord, polygon};
+
+    use super::*;
+    use crate::geometry::DepareLayer;
+    use crate::lod::LodTiles;
+
+    fn settings(two_shades: bool) -> SafetySettings {
+        SafetySettings { draft: 2.0, safety_depth: 5.0, shallow_contour: 2.0, safety_contour: 5.0, deep_contour: 10.0, two_shades }
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        polygon![(x: x, y: y), (x: x + size, y: y), (x: x + size, y: y + size), (x: x, y: y + size), (x: x, y: y)]
+    }
+
+    fn chart(areas: Vec<(Polygon, f64)>) -> DEPARE {
+        let layers = areas.into_iter().map(|(polygon, drval1)| DepareLayer::new(Color::BLACK, (drval1, drval1 + 5.0), polygon)).collect();
+        DEPARE { layers, extent: LayerExtent::default(), tiles: LodTiles::empty() }
+    }
+
+    fn contour(charts: Vec<DEPARE>, features: &ContourFeatures) -> Vec<Vec<(f32, f32)>> {
+        let safety = settings(false);
+        let map: HashMap<u16, Vec<DEPARE>> = charts.into_iter().enumerate().map(|(i, x)| (i as u16, vec![x])).collect();
+        let index = DepthIndex::new(&map, &[], Vec::new(), &safety);
+        let mut lines: Vec<Vec<(f32, f32)>> = safety_contour_lines(&map, features, &index, &safety)
+            .into_iter()
+            .map(|line| {
+                let mut line: Vec<(f32, f32)> = line.iter().map(|x| (x.x, x.y)).collect();
+                // which way round a line comes out depends on the ring it came from
+                if line.first() > line.last() {
+                    line.reverse();
+                }
+                line
+            })
+            .collect();
+        lines.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        lines
+    }
+
+    #[test]
+    fn shades() {
+        let safety = settings(false);
+        for (depth, color) in [(-0.5, DEPIT), (0.0, DEPVS), (1.9, DEPVS), (2.0, DEPMS), (4.9, DEPMS), (5.0, DEPMD), (9.9, DEPMD), (10.0, DEPDW), (50.0, DEPDW)] {
+            assert_eq!(safety.color_for_depth(depth), color, "{depth}");
+        }
+        let safety = settings(true);
+        for (depth, color) in [(-0.5, DEPIT), (0.0, DEPVS), (4.9, DEPVS), (5.0, DEPDW), (10.0, DEPDW)] {
+            assert_eq!(safety.color_for_depth(depth), color, "{depth}");
+        }
+    }
+
+    #[test]
+    fn safe() {
+        let safety = settings(false);
+        assert!(!safety.is_safe(4.99));
+        assert!(safety.is_safe(5.0));
+        assert!(safety.is_safe(20.0));
+    }
+
+    #[test]
+    fn between_depth_areas() {
+        // shallow | safe | deeper, only the edge between shallow and safe water is the contour
+        let charts = vec![chart(vec![(square(0.0, 0.0, 10.0), 0.0), (square(10.0, 0.0, 10.0), 5.0), (square(20.0, 0.0, 10.0), 10.0)])];
+        assert_eq!(contour(charts, &ContourFeatures::default()), vec![vec![(10.0, 0.0), (10.0, 10.0)]]);
+        // two shallow areas next to each other don't make one
+        let charts = vec![chart(vec![(square(0.0, 0.0, 10.0), 0.0), (square(10.0, 0.0, 10.0), 2.0)])];
+        assert_eq!(contour(charts, &ContourFeatures::default()), Vec::<Vec<(f32, f32)>>::new());
+    }
+
+    #[test]
+    fn against_land_and_dredged_areas() {
+        let charts = || vec![chart(vec![(square(0.0, 0.0, 10.0), 5.0)])];
+        // land along the top
+        let features = ContourFeatures { shallow_areas: vec![square(0.0, 10.0, 10.0)], ..ContourFeatures::default() };
+        assert_eq!(contour(charts(), &features), vec![vec![(0.0, 10.0), (10.0, 10.0)]]);
+        // a safe dredged area along the right doesn't make one
+        let features = ContourFeatures { safe_areas: vec![square(10.0, 0.0, 10.0)], ..ContourFeatures::default() };
+        assert_eq!(contour(charts(), &features), Vec::<Vec<(f32, f32)>>::new());
+    }
+
+    #[test]
+    fn against_another_chart() {
+        // the other chart's edge has an extra point in it, so the edges aren't shared and the shallow water is found by looking past it
+        let mut shallow = square(10.0, 0.0, 10.0);
+        shallow.exterior_mut(|ring| ring.0.insert(4, coord! { x: 10.0, y: 5.0 }));
+        let charts = vec![chart(vec![(square(0.0, 0.0, 10.0), 5.0)]), chart(vec![(shallow, 0.0)])];
+        assert_eq!(contour(charts, &ContourFeatures::default()), vec![vec![(10.0, 0.0), (10.0, 10.0)]]);
+        // with nothing past the edge of the cell there's no contour
+        let charts = vec![chart(vec![(square(0.0, 0.0, 10.0), 5.0)])];
+        assert_eq!(contour(charts, &ContourFeatures::default()), Vec::<Vec<(f32, f32)>>::new());
+    }
+
+    #[test]
+    fn depth_contours() {
+        let charts = vec![chart(vec![(square(0.0, 0.0, 10.0), 5.0)])];
+        let line = |y: f64| LineString::from(vec![(2.0, y), (8.0, y)]);
+        let features = ContourFeatures { contours: vec![(line(2.0), 5.0), (line(4.0), 10.0), (line(6.0), 4.995)], ..ContourFeatures::default() };
+        assert_eq!(contour(charts, &features), vec![vec![(2.0, 2.0), (8.0, 2.0)], vec![(2.0, 6.0), (8.0, 6.0)]]);
+    }
+}
@@ -6,59 +6,100 @@ use gdal::version::VersionInfo;
 use gdal::vector::{LayerAccess, OGRwkbGeometryType::*, Layer};
 
 use geo::triangulate_spade::Triangles;
-use geo::{Polygon, LineString, TriangulateEarcut, CoordsIter};
+use geo::{BoundingRect, Coord, Polygon, LineString, MapCoords, TriangulateEarcut, CoordsIter};
 
 use log::{debug, info, warn};
-use sfml::graphics::{Color, Font, RenderWindow, Vertex, View};
+use sfml::graphics::{Color, RenderTarget, Vertex, View};
 use sfml::system::Vector2f;
 use sfml::SfBox;
 
-use crate::transform::mercator_transform;
-use crate::render::{draw_vertex_vector, render_soundg};
+use crate::area::{get_area_symbols, AreaFill, AreaStyle, AreaSymbol, Hatch};
+use crate::attributes::Attributes;
+use crate::convert::ConversionStats;
+use crate::depth::SafetySettings;
+use crate::line::LineGeometry;
+use crate::transform::Projection;
+use crate::render::{draw_vertex_vector, get_world_per_pixel, is_extent_in_view};
+use crate::lod::LodTiles;
 
-use crate::config::get_resolution;
+use crate::config::Config;
 
 use std::collections::HashMap;
 
 pub trait Plotable {
-    fn render(&self, window: &mut RenderWindow, window_view: &SfBox<View>) -> ();
+    fn render(&self, window: &mut dyn RenderTarget, window_view: &SfBox<View>) -> ();
 
+    /// draws the level of detail that suits `world_per_pixel`, for layers that keep simplified copies of themselves
+    fn render_at_scale(&self, window: &mut dyn RenderTarget, window_view: &SfBox<View>, _world_per_pixel: f32) {
+        self.render(window, window_view)
+    }
 }
 pub struct PlotGeometry{
     pub polygons: Vec<Polygon>,
-    pub triangles: Triangles<f64>,
     pub color: sfml::graphics::Color,
     pub layer_name: String,
-    pub vertex_vec: Vec<Vertex>,
     pub extent: LayerExtent,
+    pub lines: LineGeometry,
+    // the triangles on the GPU cut up for culling, plus simplified copies for zooming out
+    pub tiles: LodTiles,
+    pub style: AreaStyle,
+    pub hatch: Hatch,
+    // symbols in the middle of the areas, in world coordinates
+    pub symbols: Vec<(Vector2f, AreaSymbol)>,
 }
 impl PlotGeometry {
-    pub fn new(polygons: Vec<Polygon>, triangles: Triangles<f64>, color: sfml::graphics::Color, layer_name: String, vertex_vec: Vec<Vertex>, extent: LayerExtent) -> PlotGeometry {
+    pub fn new(polygons: Vec<Polygon>, color: sfml::graphics::Color, layer_name: String, extent: LayerExtent, lines: LineGeometry) -> PlotGeometry {
         PlotGeometry {
             polygons,
-            triangles,
             color,
             layer_name,
-            vertex_vec,
-            extent
+            extent,
+            lines,
+            tiles: LodTiles::empty(),
+            style: AreaStyle::default(),
+            hatch: Hatch::default(),
+            symbols: Vec::new(),
         }
     }
     pub fn triangulate_and_scale(&mut self) {
-        self.triangles = triangles_from_scaled_polygons(&self.polygons);
+        // nothing to triangulate when the areas aren't filled, the extent is still wanted for culling the hatch
+        if self.style.fill != AreaFill::Solid {
+            for rect in self.polygons.iter().filter_map(|x| x.bounding_rect()) {
+                self.extent.MinX = self.extent.MinX.min(rect.min().x as f32);
+                self.extent.MaxX = self.extent.MaxX.max(rect.max().x as f32);
+                self.extent.MinY = self.extent.MinY.min(rect.min().y as f32);
+                self.extent.MaxY = self.extent.MaxY.max(rect.max().y as f32);
+            }
+            return;
+        }
+        let triangles = triangles_from_scaled_polygons(&self.polygons);
         let mut vertex_vec: Vec<Vertex> = Vec::new();
-        for  triangle in self.triangles.iter() {
+        for  triangle in triangles.iter() {
             vertex_vec.push(Vertex::with_pos_color((triangle.0.x as f32, triangle.0.y as f32).into(), self.color));
             vertex_vec.push(Vertex::with_pos_color((triangle.1.x as f32, triangle.1.y as f32).into(), self.color));
             vertex_vec.push(Vertex::with_pos_color((triangle.2.x as f32, triangle.2.y as f32).into(), self.color));
         }
-        self.vertex_vec = vertex_vec;
-        self.extent = get_vertices_extent(&self.vertex_vec);
+        self.extent = get_vertices_extent(&vertex_vec);
+        // only the tiles keep the triangles, there's no copy of them left on the CPU
+        self.tiles = LodTiles::build(&self.polygons, &vec![self.color; self.polygons.len()], &vertex_vec);
     }
 }
 
 impl Plotable for PlotGeometry {
-    fn render(&self, window: &mut RenderWindow, window_view: &SfBox<View>) {
-        draw_vertex_vector(window, &self.vertex_vec, &self.extent, window_view)
+    fn render(&self, window: &mut dyn RenderTarget, window_view: &SfBox<View>) {
+        let world_per_pixel = get_world_per_pixel(window_view, (window.size().x, window.size().y));
+        self.render_at_scale(window, window_view, world_per_pixel)
+    }
+
+    fn render_at_scale(&self, window: &mut dyn RenderTarget, window_view: &SfBox<View>, world_per_pixel: f32) {
+        // lines are drawn in their own pass so they end up on top of DEPARE
+        match self.style.fill {
+            AreaFill::Solid => draw_vertex_vector(window, self.tiles.for_scale(world_per_pixel), window_view),
+            AreaFill::Hatch if is_extent_in_view(window_view, &self.extent) => {
+                self.hatch.render(window, window_view, &self.polygons, &self.style, self.color, world_per_pixel)
+            }
+            _ => {}
+        }
     }
 }
 #[allow(non_snake_case)]
@@ -76,11 +117,23 @@ impl LayerExtent {
     }
 }
 // creates a PlotGeometry from a layer name - still needs to be triangulated and scaled
-pub fn get_plotgeo_from_layer_in_dataset(layer_name: &String, ds: & Dataset, color: sfml::graphics::Color) -> PlotGeometry {
+pub fn get_plotgeo_from_layer_in_dataset(layer_name: &String, ds: & Dataset, color: sfml::graphics::Color, config: &Config, projection: &dyn Projection) -> PlotGeometry {
     let mut layers = get_layers(&ds, vec![&layer_name[..]]);
-    let polygons = get_merc_polygons_from_layers(&mut layers);
-    let triangles = Triangles::new();
-    PlotGeometry::new(polygons, triangles, color, layer_name.clone(), Vec::new(), LayerExtent::default())
+    let (polygons, mut lines) = get_projected_geometries_from_layers(&mut layers, projection);
+    let style = config.area_style_for_layer(layer_name);
+    if style.boundary {
+        lines.extend(polygons.iter().flat_map(|x| std::iter::once(x.exterior()).chain(x.interiors())).cloned());
+    }
+    let lines = LineGeometry::new(lines.iter().map(|x| x.coords().map(|c| Vector2f::new(c.x as f32, c.y as f32)).collect()).collect(), config.line_style_for_layer(layer_name));
+    // see-through fills let the depth shading under them show
+    let mut color = color;
+    color.a = (color.a as f32 * style.opacity).round() as u8;
+    let mut plotgeo = PlotGeometry::new(polygons, color, layer_name.clone(), LayerExtent::default(), lines);
+    if let Some(rule) = style.symbol {
+        plotgeo.symbols = get_area_symbols(ds, layer_name, projection, rule);
+    }
+    plotgeo.style = style;
+    plotgeo
 }
 
 pub fn get_dataset(path: &str) -> Result<Dataset, gdal::errors::GdalError>{
@@ -110,13 +163,13 @@ pub fn get_layers<'a> (ds: &'a Dataset, names: Vec<&'a str>) -> Vec<Layer <'a>>
 pub fn triangles_from_scaled_polygons(polygons: &Vec<Polygon>) -> Triangles<f64> {
     let mut triangles: Triangles<f64> = Vec::new();
     for poly in polygons {
-        let scaled_coords: Vec<(f64, f64)> = poly.exterior().coords_iter().map(|x: geo_types::Coord| (x.x, x.y)).collect();
-        let new_poly = Polygon::new(LineString::from(scaled_coords), vec![]);
-        if new_poly.exterior().coords_count() < 4 {
+        if poly.exterior().coords_count() < 4 {
             warn!("Polygon has less than 4 points! Continuing..");
-            warn!("Polygon before transformation had {} points", poly.exterior().coords_count());
             continue;
         }
+        // holes need at least a triangle too or earcut will choke on them
+        let interiors: Vec<LineString> = poly.interiors().iter().filter(|x| x.coords_count() >= 4).cloned().collect();
+        let new_poly = Polygon::new(poly.exterior().clone(), interiors);
         let scaled_triangles = new_poly.earcut_triangles();
         //debug!("Found {} triangles", scaled_triangles.len());
         for scaled_triangle in scaled_triangles {
@@ -126,218 +179,173 @@ pub fn triangles_from_scaled_polygons(polygons: &Vec<Polygon>) -> Triangles<f64>
     triangles
 }
 
-/// performs a mercator transform on all the geometries in a layer
-pub fn get_merc_polygons_from_layers(layers: &mut Vec<gdal::vector::Layer>) -> Vec<Polygon> {
+/// projects a converted geometry out of lon/lat
+pub fn project_geometry(geometry: geo::Geometry, projection: &dyn Projection) -> geo::Geometry {
+    geometry.map_coords(|c| {
+        let (x, y) = projection.forward((c.x, c.y));
+        Coord { x, y }
+    })
+}
+
+/// pulls every area out of a geometry, holes included
+pub fn polygons_from_geo_geometry(geometry: geo::Geometry) -> Vec<Polygon> {
+    match geometry {
+        geo::Geometry::Polygon(poly) => vec![poly],
+        geo::Geometry::MultiPolygon(multi) => multi.0,
+        geo::Geometry::GeometryCollection(collection) => collection.0.into_iter().flat_map(polygons_from_geo_geometry).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// the exterior ring of a polygon then its holes
+pub(crate) fn rings(polygon: &Polygon) -> impl Iterator<Item = &LineString> {
+    std::iter::once(polygon.exterior()).chain(polygon.interiors())
+}
+
+/// pulls every line out of a geometry, these get drawn as lines rather than filled
+pub fn lines_from_geo_geometry(geometry: geo::Geometry) -> Vec<LineString> {
+    match geometry {
+        geo::Geometry::LineString(line) => vec![line],
+        geo::Geometry::MultiLineString(multi) => multi.0,
+        geo::Geometry::GeometryCollection(collection) => collection.0.into_iter().flat_map(lines_from_geo_geometry).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// projects all the geometries in a layer, split into areas and lines
+pub fn get_projected_geometries_from_layers(layers: &mut Vec<gdal::vector::Layer>, projection: &dyn Projection) -> (Vec<Polygon>, Vec<LineString>) {
     let mut polygons: Vec<Polygon> = Vec::new();
-    let merc_scale = get_resolution();
+    let mut lines: Vec<LineString> = Vec::new();
     debug!("get_polygons_from_layers called! with {} layers", layers.len());
     for layer in layers {
         let layer_name = layer.name().clone();
         debug!("Checking layer {}", layer_name);
+        let mut stats = ConversionStats::default();
         for feature in layer.features()
         {
-            let geometry = match feature.geometry() {
+            let geometry = match stats.convert(feature.geometry()) {
                 Some(geo) => geo,
-                None => {
-                    debug!("[get_extent_from_layers] {} has no geometry!", layer_name);
-                    continue;
-                }
+                None => continue,
             };
-            let geom_type_name = geometry.geometry_name();
-            let geo_count = geometry.geometry_count();
-            debug!("{} has {} geometries", layer_name, geo_count);
-            for i in 0..geo_count{
-                let new_geo = geometry.get_geometry(i);
-                let new_geo_name = new_geo.geometry_name();
-                match new_geo.geometry_type() {
-                    wkbLinearRing | wkbLineString | wkbPolygon => {
-                        debug!("Matched {new_geo_name}");
-                        let points = new_geo.get_point_vec();
-                        let num_points = points.len();
-                        let merc_points_2d: Vec<(f64, f64)> = points.iter().map(|x| (x.0, x.1)).map(|x| mercator_transform(x, merc_scale)).collect();
-                        let poly = Polygon::new(LineString::from(merc_points_2d), vec![]);
-                        let num_poly_points = poly.exterior().coords_count();
-                        polygons.push(poly);
-                        debug!("Added a new geometry to the list with {} points before transform {}", num_poly_points, num_points);
-                    },
-                    unsure => {
-                        debug!("{geom_type_name} is {unsure}");
-                    }
-                }
+            let geometry = project_geometry(geometry, projection);
+            let feature_polygons = polygons_from_geo_geometry(geometry.clone());
+            let feature_lines = lines_from_geo_geometry(geometry);
+            if feature_polygons.is_empty() && feature_lines.is_empty() {
+                stats.skipped += 1;
+            }
+            for poly in feature_polygons {
+                let num_poly_points = poly.exterior().coords_count();
+                debug!("Added a new geometry to the list with {} points and {} holes", num_poly_points, poly.interiors().len());
+                polygons.push(poly);
             }
+            lines.extend(feature_lines);
         }
+        stats.report(&layer_name);
     }
-    polygons
+    (polygons, lines)
 }
 
-fn get_color_for_depth(depth: f64) -> Color {
-    // 19 deep
-    // 9.5 safe
-    // 6.5 shallow
-    if depth > 19.0 {
-        return Color::rgb(212,234,238)
-    }
-    else if depth > 6.5 {
-        return Color::rgb(186,213,225)
-    }
-    else if depth <= 6.5 {
-        return Color::rgb(115,182,239)
-    }
-    Color::BLACK
-}
-
-
-pub fn get_depare_from_layer(layer: &mut gdal::vector::Layer) -> DEPARE {
+pub fn get_depare_from_layer(layer: &mut gdal::vector::Layer, projection: &dyn Projection, safety: &SafetySettings) -> DEPARE {
     let mut depare_layers: Vec<DepareLayer> = Vec::new();
-    let merc_scale = get_resolution();
     let layer_name = layer.name().clone();
     let mut vertex_vec = Vec::new();
-    let extent: LayerExtent = LayerExtent { MinX: f32::MAX, MaxX: f32::MIN, MinY: f32::MAX, MaxY: f32::MIN };
     debug!("Checking layer {}", layer_name);
+    let mut stats = ConversionStats::default();
     for feature in layer.features()
     {
-        let geometry = match feature.geometry() {
+        let geometry = match stats.convert(feature.geometry()) {
             Some(geo) => geo,
-            None => {
-                debug!("[get_extent_from_layers] {} has no geometry!", layer_name);
-                continue;
-            }
+            None => continue,
         };
-        let min_value = match feature.field_as_double_by_name("DRVAL1") {
-            Ok(val) => {
-                match val {
-                    Some(val) => val,
-                    None => {
-                        warn!("No DRVALUE1 field found in DEPARE layer!");
-                        continue;
-                    }
-                }
-            }
-            Err(_) => {
-                warn!("No DRVALUE1 field found in DEPARE layer!");
+        let attributes = Attributes::new(&feature);
+        let (min_value, max_value) = match (attributes.float("DRVAL1"), attributes.float("DRVAL2")) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            (min_value, _) => {
+                warn!("No {} field found in DEPARE layer!", if min_value.is_none() { "DRVAL1" } else { "DRVAL2" });
+                stats.skipped += 1;
                 continue;
             }
         };
-        let max_value = match feature.field_as_double_by_name("DRVAL2") {
-            Ok(val) => {
-                match val {
-                    Some(val) => val,
-                    None => {
-                        warn!("No DRVALUE2 field found in DEPARE layer!");
-                        continue;
-                    }
-                }
-            }
-            Err(_) => {
-                warn!("No DRVALUE2 field found in DEPARE layer!");
+
+        let feature_polygons = polygons_from_geo_geometry(project_geometry(geometry, projection));
+        if feature_polygons.is_empty() {
+            stats.skipped += 1;
+        }
+        for poly in feature_polygons {
+            let num_poly_points = poly.exterior().coords_count();
+            let num_holes = poly.interiors().len();
+            // todo take care of case with 3 points
+            if num_poly_points < 4 {
+                warn!("Polygon has less than 4 points! Continuing..");
                 continue;
             }
-        };
-
-        let geom_type_name = geometry.geometry_name();
-        let geo_count = geometry.geometry_count();
-        debug!("{} has {} geometries", layer_name, geo_count);
-        for i in 0..geo_count{
-            let new_geo = geometry.get_geometry(i);
-            let new_geo_name = new_geo.geometry_name();
-            match new_geo.geometry_type() {
-                wkbLinearRing | wkbLineString | wkbPolygon => {
-                    debug!("Matched {new_geo_name}");
-                    let points = new_geo.get_point_vec();
-                    let num_points = points.len();
-                    
-                    // handle the points
-                    let merc_points_2d: Vec<(f64, f64)> = points.iter().map(|x| (x.0, x.1)).map(|x| mercator_transform(x, merc_scale)).collect();
-
-                    let poly = Polygon::new(LineString::from(merc_points_2d), vec![]);
-                    let num_poly_points = poly.exterior().coords_count();
-                    let polygon_vec = vec![poly];
-                    // todo take care of case with 3 points
-                    if num_poly_points < 4 {
-                        warn!("Polygon has less than 4 points! Continuing..");
-                        continue;
-                    }
-                    let color: Color;
-                    // convert m to f
-                    let foot_depth = min_value * 3.281;
-                    color = get_color_for_depth(foot_depth); 
-                    let poly_triangles = triangles_from_scaled_polygons(&polygon_vec);
-                    for  triangle in poly_triangles.iter() {
-                        vertex_vec.push(Vertex::with_pos_color((triangle.0.x as f32, triangle.0.y as f32).into(), color));
-                        vertex_vec.push(Vertex::with_pos_color((triangle.1.x as f32, triangle.1.y as f32).into(), color));
-                        vertex_vec.push(Vertex::with_pos_color((triangle.2.x as f32, triangle.2.y as f32).into(), color));
-                    }
-
-                    let color = Color::WHITE;
-                    let depth = (min_value, max_value);
-                    let depare_layer = DepareLayer::new(vec![], color, depth);
-                    depare_layers.push(depare_layer);
-                    debug!("Added a new geometry to the list with {} points before transform {} with depth ranging from {min_value}-{max_value}m", num_poly_points, num_points);
-                },
-                unsure => {
-                    debug!("{geom_type_name} is {unsure}");
-                }
+            let color = safety.color_for_depth(min_value);
+            let poly_triangles = triangles_from_scaled_polygons(&vec![poly.clone()]);
+            for  triangle in poly_triangles.iter() {
+                vertex_vec.push(Vertex::with_pos_color((triangle.0.x as f32, triangle.0.y as f32).into(), color));
+                vertex_vec.push(Vertex::with_pos_color((triangle.1.x as f32, triangle.1.y as f32).into(), color));
+                vertex_vec.push(Vertex::with_pos_color((triangle.2.x as f32, triangle.2.y as f32).into(), color));
             }
+
+            let depth = (min_value, max_value);
+            // the polygon is kept around for answering depth queries, the triangles above are what gets drawn
+            let depare_layer = DepareLayer::new(color, depth, poly);
+            depare_layers.push(depare_layer);
+            debug!("Added a new geometry to the list with {} points and {} holes with depth ranging from {min_value}-{max_value}m", num_poly_points, num_holes);
         }
     }
-    let mut d = DEPARE { layers: depare_layers, vertices: vertex_vec, extent: extent };
-    d.sum_vertices();
-    let extent = get_vertices_extent(&d.vertices);
-    d.extent = extent;
+    stats.report(&layer_name);
+    let extent = get_vertices_extent(&vertex_vec);
+    let mut d = DEPARE { layers: depare_layers, extent, tiles: LodTiles::empty() };
+    // neighbouring areas share edges, simplifying them all together keeps them meeting up
+    let polygons: Vec<Polygon> = d.layers.iter().map(|x| x.polygon.clone()).collect();
+    let colors: Vec<Color> = d.layers.iter().map(|x| x.color).collect();
+    d.tiles = LodTiles::build(&polygons, &colors, &vertex_vec);
     d
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct DEPARE {
     pub layers: Vec<DepareLayer>,
-    pub vertices: Vec<Vertex>,
     pub extent: LayerExtent,
+    pub tiles: LodTiles,
 }
 
-impl DEPARE {
-    pub fn sum_vertices(&mut self) {
-        for layer in &self.layers {
-            for vertex in &layer.vertices {
-                self.vertices.push(*vertex);
-            }
-        }
+impl Plotable for DEPARE {
+    fn render(&self, window: &mut dyn RenderTarget, view: &SfBox<View>) -> () {
+        let world_per_pixel = get_world_per_pixel(view, (window.size().x, window.size().y));
+        self.render_at_scale(window, view, world_per_pixel)
     }
-}
 
-impl Plotable for DEPARE {
-    fn render(&self, window: &mut RenderWindow, view: &SfBox<View>) -> () {
-        draw_vertex_vector(window, &self.vertices, &self.extent, view)
+    fn render_at_scale(&self, window: &mut dyn RenderTarget, view: &SfBox<View>, world_per_pixel: f32) {
+        draw_vertex_vector(window, self.tiles.for_scale(world_per_pixel), view)
     }
 }
 #[derive(Clone, Debug)]
 pub struct DepareLayer {
-    pub vertices: Vec<Vertex>,
     pub color: Color,
+    // DRVAL1, DRVAL2 in meters
     pub depth: (f64, f64),
+    // projected into world coordinates
+    pub polygon: Polygon,
 }
 
 impl DepareLayer {
-    pub fn new(vertices: Vec<Vertex>, color: Color, depth: (f64, f64)) -> DepareLayer {
-        DepareLayer { vertices, color, depth }
+    pub fn new(color: Color, depth: (f64, f64), polygon: Polygon) -> DepareLayer {
+        DepareLayer { color, depth, polygon }
     }
 }
 
 pub struct DepthLayer {
     pub coordinates: Vec<(f64, f64, f64)>,
-    pub font: sfml::SfBox<Font>,
     pub longitude_scale: (f64, f64),
     pub latitude_scale: (f64, f64),
     pub extent: LayerExtent,
     pub color: Option<Color>,
 }
-pub struct BuoyLayer {
-    pub vertices: Vec<Vertex>,
-    pub extent: LayerExtent,
-}
 
 impl DepthLayer {
-    pub fn project_coords(&mut self) {
-        let merc_scale = get_resolution();
+    pub fn project_coords(&mut self, projection: &dyn Projection) {
         let mut final_points: Vec<(f64, f64, f64)> = Vec::new();
         let mut min_x = f64::MAX;
         let mut max_x = f64::MIN;
@@ -345,7 +353,7 @@ impl DepthLayer {
         let mut max_y = f64::MIN;
 
         for point in &self.coordinates {
-            let merc_point = mercator_transform((point.0, point.1), merc_scale);
+            let merc_point = projection.forward((point.0, point.1));
             let (x, y) = merc_point;
             if  x < min_x {
                 min_x = x;
@@ -379,12 +387,6 @@ impl DepthLayer {
     }
 }
 
-impl Plotable for DepthLayer {
-    fn render(&self, window: &mut RenderWindow, view: &SfBox<View>) -> () {
-        render_soundg(window, self, &self.font, 0.0 as f32, view)
-    }
-    
-}
 pub fn get_soundg_layer(ds: & Dataset) -> Option<Layer> {
     let mut layers = get_layers(&ds, vec!["SOUNDG"]);
     let layer = layers.pop();
@@ -438,80 +440,11 @@ pub fn get_soundg_coords(soundg_layer: &mut Layer) -> DepthLayer {
             }
         }
     }
-    DepthLayer { coordinates: final_points, font: get_default_font(), longitude_scale: (f64::MAX, f64::MIN), latitude_scale: (f64::MAX, f64::MIN), extent: LayerExtent::default(), color: None} 
+    DepthLayer { coordinates: final_points, longitude_scale: (f64::MAX, f64::MIN), latitude_scale: (f64::MAX, f64::MIN), extent: LayerExtent::default(), color: None} 
 } 
 
 
 
-pub fn get_default_font() -> sfml::SfBox<Font> {
-    Font::from_file("./src/fonts/OpenSans-Regular.ttf").unwrap() 
-}
-
-pub fn get_buoy_data(layer: &mut Layer, scale: (u32, u32)) -> BuoyLayer {
-    let mut final_points: Vec<Vertex> = Vec::new();
-    #[allow(unused_assignments)]
-    let mut color = Color::BLACK;
-    for feature in layer.features() {
-        let color_map = HashMap::from([(1, Color::WHITE), (3, Color::RED), (4, Color::GREEN), (5, Color::BLUE), (6, Color::YELLOW)]);
-        let point =  match feature.geometry().unwrap().get_point_vec().pop() {
-            Some(point) => point,
-            None => {
-                warn!("No point found in BUOY layer!");
-                continue;
-            }
-        };
-        let merc_point = mercator_transform((point.0, point.1), scale);
-        
-        let color_value =  match feature.field_as_string_by_name("COLOUR") {
-            Ok(val) => {
-                match val {
-                    Some(val) => val,
-                    None => {
-                        warn!("No COLOUR field found in BUOY layer!");
-                        continue;
-                    }
-                }
-            }
-            Err(_) => {
-                warn!("No COLOUR field found in BUOY layer!");
-                continue;
-            }
-        };
-        let first_digit_of_string = color_value.chars().next().unwrap();
-        let color_value =  match first_digit_of_string {
-            '(' => 
-            {
-                let color_value = color_value.trim_matches(|c| c == '(' || c == ')');
-                let c_v =  match color_value.chars().rev().next() 
-                {
-                    Some(c) => c.to_digit(10).unwrap_or(50),
-                    None => 
-                    {
-                        warn!("No color found for value {} in COLOUR field!", color_value);
-                        continue;
-                    }
-                };
-                c_v
-            } 
-            _ => {
-                warn!("No color found for value {} in COLOUR field!", color_value);
-                continue;
-            }
-        };
-
-        color = match color_map.get(&color_value) {
-            Some(color) => color.clone(),
-            None => {
-                warn!("No color found for value {} in COLOUR map!", color_value);
-                continue;
-            }
-        };
-        final_points.push(Vertex::with_pos_color(Vector2f::from((merc_point.0 as f32, merc_point.1 as f32)), color));
-    }
-    let extent = get_vertices_extent(&final_points);
-    BuoyLayer { vertices: final_points, extent: extent}
-    }
-
 pub fn does_extent_collide(extent1: &LayerExtent, extent2: &LayerExtent) -> bool {
     // return true if envelope 2 collides with envelope 1
     // if e2 is higher than e1
@@ -541,7 +474,7 @@ pub fn does_extent_collide(extent1: &LayerExtent, extent2: &LayerExtent) -> bool
     true
 }
 
-pub fn get_vertices_extent(vertices: &Vec<Vertex>) -> LayerExtent {
+pub fn get_vertices_extent(vertices: &[Vertex]) -> LayerExtent {
     let mut min_x = f32::MAX;
     let mut max_x = f32::MIN;
     let mut min_y = f32::MAX;
@@ -634,14 +567,17 @@ pub fn get_extent_area(extent: &LayerExtent) -> f32 {
     x.abs() * y.abs() 
 }
 
-pub fn get_hashmap_of_depare_layers(depare_layers: &mut Vec<(DEPARE, String)>) -> HashMap<u16, Vec<DEPARE>> {
+pub fn get_hashmap_of_depare_layers(depare_layers: Vec<(DEPARE, String)>) -> HashMap<u16, Vec<DEPARE>> {
     // create map of datasets
     let mut map: HashMap<u16, Vec<DEPARE>>  = HashMap::new();
-    let depare_copy = depare_layers.clone();
+    // the overlaps only need names and extents, the layers themselves hold vertex buffers and get moved into the map
+    let names: Vec<String> = depare_layers.iter().map(|(_depare, s)| s.clone()).collect();
     let mut name2extent: HashMap<String, LayerExtent> = HashMap::new();
-    
+    let mut name2depare: HashMap<String, DEPARE> = HashMap::new();
+
     for (depare_layer, s) in depare_layers {
         name2extent.insert(s.clone(), depare_layer.extent.clone());
+        name2depare.insert(s, depare_layer);
         }
     debug!("Looking at Map!");
     for k in name2extent.keys() {
@@ -670,17 +606,14 @@ pub fn get_hashmap_of_depare_layers(depare_layers: &mut Vec<(DEPARE, String)>) -
         b_area.partial_cmp(&a_area).unwrap() // sort it backwards - so smallest are rendered last
         });
         debug!("Sorted Vec: {:?}", sort_vec);
-        let depare_vec: Vec<DEPARE> = sort_vec.iter().map(|x: &String| {
-            let layer = depare_copy.iter().find(|(_depare, s)| s == x).unwrap();
-            layer.0.clone()
-        }).collect();
+        let depare_vec: Vec<DEPARE> = sort_vec.iter().map(|x: &String| name2depare.remove(x).unwrap()).collect();
         map.insert(cntr, depare_vec);
         cntr += 1;
     }
     let mut collision_free_vec = Vec::new();
-    for layer_name in depare_copy {
-        if !total_cols.contains(&layer_name.1) {
-            collision_free_vec.push(layer_name.0);
+    for layer_name in names {
+        if !total_cols.contains(&layer_name) {
+            collision_free_vec.push(name2depare.remove(&layer_name).unwrap());
         }
     }
     map.insert(0, collision_free_vec);
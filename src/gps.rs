@@ -0,0 +1,466 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+
+use crate::config::GpsConfig;
+use crate::nmea::{parse_sentence, Gga, Hdg, NmeaError, NmeaTime, Rmc, Sentence};
+use crate::transform::{format_lat_lon, EARTH_MEAN_RADIUS};
+use crate::units::{Units, METERS_PER_NAUTICAL_MILE};
+
+// how long we wait before trying a serial port or tcp connection again
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+// a fix older than this isn't trusted anymore
+pub const FIX_TIMEOUT: Duration = Duration::from_secs(10);
+// gaps in a log longer than this are skipped over instead of waited out
+const MAX_REPLAY_GAP: f64 = 5.0;
+// the simulator reports once a second of simulated time
+const SIMULATOR_STEP: f64 = 1.0;
+
+pub const GPS_USAGE: &str = "usage: chartplotter [--gps SOURCE] [--speed MULTIPLIER]
+  SOURCE is serial:DEVICE, tcp:HOST:PORT, udp:PORT, file:LOG.nmea or sim:LON,LAT,COURSE,KNOTS";
+
+/// Where NMEA sentences come from, written in config as `kind:where`
+#[derive(Clone, Debug, PartialEq)]
+pub enum GpsSource {
+    // e.g. serial:/dev/ttyUSB0 - the port needs its baud rate set beforehand, e.g. `stty -F /dev/ttyUSB0 4800`
+    Serial(String),
+    // e.g. tcp:192.168.1.10:10110
+    Tcp(String),
+    // e.g. udp:10110 or udp:0.0.0.0:10110
+    Udp(String),
+    // e.g. file:logs/sail.nmea, replayed using the time stamps in the log
+    File(String),
+    // e.g. sim:-122.45,37.80,270,6 sails from a start point at a fixed course and speed
+    Simulator(Simulator),
+}
+
+impl GpsSource {
+    pub fn from_spec(spec: &str) -> Option<GpsSource> {
+        let (kind, target) = spec.split_once(':')?;
+        if target.is_empty() {
+            return None;
+        }
+        match kind {
+            "serial" => Some(GpsSource::Serial(target.to_string())),
+            "tcp" => Some(GpsSource::Tcp(target.to_string())),
+            "udp" if target.contains(':') => Some(GpsSource::Udp(target.to_string())),
+            "udp" => Some(GpsSource::Udp(format!("0.0.0.0:{target}"))),
+            "file" => Some(GpsSource::File(target.to_string())),
+            "sim" => Simulator::from_spec(target).map(GpsSource::Simulator),
+            _ => None,
+        }
+    }
+}
+
+fn handle_line(line: &str, sender: &Sender<Sentence>) -> bool {
+    match parse_sentence(line) {
+        Ok(sentence) => sender.send(sentence).is_ok(),
+        Err(NmeaError::Unsupported(_)) => true,
+        Err(e) => {
+            debug!("Skipping '{}': {e}", line.trim());
+            true
+        }
+    }
+}
+
+/// sends every sentence in a stream, returns false once nobody is listening anymore
+fn read_lines(reader: impl Read, sender: &Sender<Sentence>) -> bool {
+    for line in BufReader::new(reader).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("GPS read failed: {e}");
+                return true;
+            }
+        };
+        if !handle_line(&line, sender) {
+            return false;
+        }
+    }
+    true
+}
+
+fn read_udp(address: &str, sender: &Sender<Sentence>) -> bool {
+    let socket = match UdpSocket::bind(address) {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("Couldn't listen for GPS on udp {address}: {e}");
+            return true;
+        }
+    };
+    let mut buffer = [0u8; 4096];
+    loop {
+        let size = match socket.recv(&mut buffer) {
+            Ok(size) => size,
+            Err(e) => {
+                warn!("GPS udp read failed: {e}");
+                return true;
+            }
+        };
+        // a datagram can carry several sentences
+        for line in String::from_utf8_lossy(&buffer[..size]).lines() {
+            if !handle_line(line, sender) {
+                return false;
+            }
+        }
+    }
+}
+
+/// plays a log back at `speed` times the pace it was recorded at, going by the sentence time stamps
+fn read_file(path: &str, speed: f64, sender: &Sender<Sentence>) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Couldn't open NMEA log {path}: {e}");
+            return;
+        }
+    };
+    let mut last_time: Option<f64> = None;
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let sentence = match parse_sentence(&line) {
+            Ok(sentence) => sentence,
+            Err(NmeaError::Unsupported(_)) => continue,
+            Err(e) => {
+                debug!("Skipping '{}': {e}", line.trim());
+                continue;
+            }
+        };
+        match sentence.time() {
+            Some(time) => {
+                let time = time.seconds_of_day();
+                if let Some(last) = last_time {
+                    // a log running past midnight starts the day over
+                    let gap = (time - last).rem_euclid(86400.0);
+                    if gap > 0.0 && gap <= MAX_REPLAY_GAP {
+                        thread::sleep(Duration::from_secs_f64(gap / speed));
+                    }
+                }
+                last_time = Some(time);
+            }
+            // logs without time stamps get a fix a second
+            None if matches!(sentence, Sentence::Rmc(_)) => thread::sleep(Duration::from_secs_f64(1.0 / speed)),
+            None => {}
+        }
+        if sender.send(sentence).is_err() {
+            return;
+        }
+    }
+    info!("Finished playing back {path}");
+}
+
+/// A boat that holds a course and speed from where it started, sailing along a rhumb line
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulator {
+    // lon, lat in degrees
+    pub position: (f64, f64),
+    // degrees true and knots
+    pub course: f64,
+    pub speed: f64,
+    // seconds since midnight UTC on the simulated clock
+    pub time: f64,
+}
+
+impl Simulator {
+    /// LON,LAT,COURSE,KNOTS
+    pub fn from_spec(spec: &str) -> Option<Simulator> {
+        let values: Vec<f64> = spec.split(',').map(|x| x.trim().parse::<f64>().ok()).collect::<Option<_>>()?;
+        match values[..] {
+            [lon, lat, course, speed] if lon.abs() <= 180.0 && lat.abs() < 90.0 && speed >= 0.0 => {
+                Some(Simulator { position: (lon, lat), course: course.rem_euclid(360.0), speed, time: 0.0 })
+            }
+            _ => None,
+        }
+    }
+
+    /// moves the boat on by `seconds`
+    pub fn step(&mut self, seconds: f64) {
+        let (lon, lat) = self.position;
+        let distance = self.speed * METERS_PER_NAUTICAL_MILE * seconds / 3600.0;
+        let course = self.course.to_radians();
+        let lat_step = (distance * course.cos() / EARTH_MEAN_RADIUS).to_degrees();
+        let mid_lat = (lat + lat_step / 2.0).to_radians();
+        let lon_step = (distance * course.sin() / (EARTH_MEAN_RADIUS * mid_lat.cos())).to_degrees();
+        self.position = ((lon + lon_step + 540.0).rem_euclid(360.0) - 180.0, lat + lat_step);
+        self.time += seconds;
+    }
+
+    /// what a receiver on the simulated boat would be sending right now
+    pub fn sentences(&self) -> Vec<Sentence> {
+        let time = Some(NmeaTime::from_seconds_of_day(self.time));
+        vec![
+            Sentence::Rmc(Rmc { time, valid: true, position: Some(self.position), sog: Some(self.speed), cog: Some(self.course), date: None, magnetic_variation: None }),
+            Sentence::Gga(Gga { time, position: Some(self.position), quality: 8, satellites: None, hdop: None, altitude: None }),
+            Sentence::Hdt(Some(self.course)),
+        ]
+    }
+}
+
+fn run_simulator(mut simulator: Simulator, speed: f64, sender: &Sender<Sentence>) {
+    loop {
+        for sentence in simulator.sentences() {
+            if sender.send(sentence).is_err() {
+                return;
+            }
+        }
+        thread::sleep(Duration::from_secs_f64(SIMULATOR_STEP / speed));
+        simulator.step(SIMULATOR_STEP);
+    }
+}
+
+/// reads the source on its own thread, sentences show up on the returned channel.
+/// `speed` only matters for logs and the simulator, live sources arrive when they arrive
+pub fn spawn_gps_reader(source: GpsSource, speed: f64) -> Receiver<Sentence> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        info!("Reading GPS from {:?}", source);
+        loop {
+            let listening = match &source {
+                GpsSource::Serial(path) => match File::open(path) {
+                    Ok(port) => read_lines(port, &sender),
+                    Err(e) => {
+                        warn!("Couldn't open GPS port {path}: {e}");
+                        true
+                    }
+                },
+                GpsSource::Tcp(address) => match TcpStream::connect(address) {
+                    Ok(stream) => read_lines(stream, &sender),
+                    Err(e) => {
+                        warn!("Couldn't connect to GPS at {address}: {e}");
+                        true
+                    }
+                },
+                GpsSource::Udp(address) => read_udp(address, &sender),
+                GpsSource::File(path) => {
+                    read_file(path, speed, &sender);
+                    return;
+                }
+                GpsSource::Simulator(simulator) => {
+                    run_simulator(simulator.clone(), speed, &sender);
+                    return;
+                }
+            };
+            if !listening {
+                return;
+            }
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+    receiver
+}
+
+/// `--gps` and `--speed` on the command line override the [gps] table in the config
+pub fn parse_gps_args(args: &[String], gps: Option<GpsConfig>) -> Result<Option<GpsConfig>, String> {
+    let mut source = None;
+    let mut speed = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gps" => {
+                let value = args.next().ok_or("--gps needs a value")?;
+                if GpsSource::from_spec(value).is_none() {
+                    return Err(format!("bad --gps source '{value}'"));
+                }
+                source = Some(value.clone());
+            }
+            "--speed" => {
+                let value = args.next().ok_or("--speed needs a value")?;
+                match value.parse::<f64>() {
+                    Ok(x) if x > 0.0 => speed = Some(x),
+                    _ => return Err(format!("bad --speed '{value}', expected a number greater than 0")),
+                }
+            }
+            unknown => return Err(format!("unknown argument '{unknown}'")),
+        }
+    }
+    let gps = match (source, gps) {
+        (Some(source), gps) => Some(GpsConfig { source, speed: gps.map_or(1.0, |gps| gps.speed) }),
+        (None, gps) => gps,
+    };
+    match (gps, speed) {
+        (Some(gps), Some(speed)) => Ok(Some(GpsConfig { speed, ..gps })),
+        (None, Some(_)) => Err("--speed needs a gps source, pass --gps or set one in [gps]".to_string()),
+        (gps, None) => Ok(gps),
+    }
+}
+
+/// What we know about our own boat, built up from whichever sentences the receiver sends
+#[derive(Clone, Debug, Default)]
+pub struct NavState {
+    // lon, lat in degrees
+    pub position: Option<(f64, f64)>,
+    // course and speed over ground in degrees true and knots
+    pub cog: Option<f64>,
+    pub sog: Option<f64>,
+    // degrees true
+    pub heading: Option<f64>,
+    pub magnetic_variation: Option<f64>,
+    pub fix_quality: u32,
+    pub fix_type: u32,
+    pub satellites_used: usize,
+    pub satellites_in_view: u32,
+    pub hdop: Option<f64>,
+    pub last_fix: Option<Instant>,
+}
+
+impl NavState {
+    fn set_position(&mut self, position: Option<(f64, f64)>) {
+        if let Some(position) = position {
+            self.position = Some(position);
+            self.last_fix = Some(Instant::now());
+        }
+    }
+
+    pub fn update(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Rmc(rmc) => {
+                if !rmc.valid {
+                    return;
+                }
+                self.set_position(rmc.position);
+                self.sog = rmc.sog.or(self.sog);
+                self.cog = rmc.cog.or(self.cog);
+                self.magnetic_variation = rmc.magnetic_variation.or(self.magnetic_variation);
+            }
+            Sentence::Gga(gga) => {
+                self.fix_quality = gga.quality;
+                if gga.quality == 0 {
+                    return;
+                }
+                self.set_position(gga.position);
+                self.hdop = gga.hdop.or(self.hdop);
+            }
+            Sentence::Gll(gll) => {
+                if gll.valid {
+                    self.set_position(gll.position);
+                }
+            }
+            Sentence::Vtg(vtg) => {
+                self.cog = vtg.cog.or(self.cog);
+                self.sog = vtg.sog.or(self.sog);
+            }
+            Sentence::Hdt(heading) => {
+                self.heading = heading.or(self.heading);
+            }
+            Sentence::Hdg(hdg) => {
+                // fall back on the variation from RMC when the compass doesn't know it
+                let variation = hdg.variation.or(self.magnetic_variation);
+                let hdg = Hdg { variation, ..hdg.clone() };
+                self.heading = hdg.true_heading().or(self.heading);
+            }
+            Sentence::Gsa(gsa) => {
+                self.fix_type = gsa.fix_type;
+                self.satellites_used = gsa.satellites.len();
+                self.hdop = gsa.hdop.or(self.hdop);
+            }
+            Sentence::Gsv(gsv) => {
+                self.satellites_in_view = gsv.satellites_in_view;
+            }
+        }
+    }
+
+    /// pulls in everything that has arrived since last time without blocking, true if anything did
+    pub fn update_from(&mut self, receiver: &Receiver<Sentence>) -> bool {
+        let mut updated = false;
+        for sentence in receiver.try_iter() {
+            self.update(&sentence);
+            updated = true;
+        }
+        updated
+    }
+
+    /// own ship lines for the readout box, nothing until the first fix
+    pub fn readout(&self, units: &Units) -> Vec<String> {
+        let (lon, lat) = match self.position {
+            Some(position) => position,
+            None => return Vec::new(),
+        };
+        let mut lines = vec![format_lat_lon(lat, lon)];
+        if !self.has_fix() {
+            lines[0].push_str(" (no fix)");
+        }
+        let dashes = || "---".to_string();
+        let sog = self.sog.map(|x| units.speed.format(x)).unwrap_or_else(dashes);
+        let cog = self.cog.map(|x| format!("{x:03.0}°")).unwrap_or_else(dashes);
+        let heading = self.heading.map(|x| format!("{x:03.0}°")).unwrap_or_else(dashes);
+        lines.push(format!("SOG {sog}  COG {cog}  HDG {heading}"));
+        lines
+    }
+
+    pub fn has_fix(&self) -> bool {
+        match self.last_fix {
+            Some(time) => time.elapsed() < FIX_TIMEOUT,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn config(source: &str, speed: f64) -> Option<GpsConfig> {
+        Some(GpsConfig { source: source.to_string(), speed })
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(GpsSource::from_spec("serial:/dev/ttyUSB0"), Some(GpsSource::Serial("/dev/ttyUSB0".to_string())));
+        assert_eq!(GpsSource::from_spec("tcp:192.168.1.10:10110"), Some(GpsSource::Tcp("192.168.1.10:10110".to_string())));
+        assert_eq!(GpsSource::from_spec("udp:10110"), Some(GpsSource::Udp("0.0.0.0:10110".to_string())));
+        assert_eq!(GpsSource::from_spec("udp:127.0.0.1:10110"), Some(GpsSource::Udp("127.0.0.1:10110".to_string())));
+        assert_eq!(GpsSource::from_spec("file:logs/sail.nmea"), Some(GpsSource::File("logs/sail.nmea".to_string())));
+        let sim = Simulator { position: (-122.45, 37.8), course: 270.0, speed: 6.0, time: 0.0 };
+        assert_eq!(GpsSource::from_spec("sim:-122.45, 37.80,-90,6"), Some(GpsSource::Simulator(sim)));
+    }
+
+    #[test]
+    fn bad_sources() {
+        for spec in ["", "serial", "serial:", "gpsd:localhost", "sim:1,2,3", "sim:1,2,3,4,5", "sim:200,0,0,5", "sim:0,90,0,5", "sim:0,0,0,-1", "sim:a,b,c,d"] {
+            assert_eq!(GpsSource::from_spec(spec), None, "{spec}");
+        }
+    }
+
+    #[test]
+    fn command_line() {
+        assert!(parse_gps_args(&[], None).unwrap().is_none());
+        let gps = parse_gps_args(&args(&["--gps", "udp:10110"]), None).unwrap().unwrap();
+        assert_eq!((gps.source.as_str(), gps.speed), ("udp:10110", 1.0));
+        // --gps keeps the config's speed, --speed keeps its source
+        let gps = parse_gps_args(&args(&["--gps", "udp:10110"]), config("file:a.nmea", 4.0)).unwrap().unwrap();
+        assert_eq!((gps.source.as_str(), gps.speed), ("udp:10110", 4.0));
+        let gps = parse_gps_args(&args(&["--speed", "10"]), config("file:a.nmea", 4.0)).unwrap().unwrap();
+        assert_eq!((gps.source.as_str(), gps.speed), ("file:a.nmea", 10.0));
+    }
+
+    #[test]
+    fn bad_command_lines() {
+        for bad in [&["--gps"][..], &["--gps", "gpsd:localhost"], &["--speed", "0"], &["--speed", "fast"], &["--speed", "2"], &["--verbose"]] {
+            assert!(parse_gps_args(&args(bad), None).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn simulator_step() {
+        // an hour due north at 6 knots is 6 minutes of latitude
+        let mut simulator = Simulator::from_spec("-122.45,37.8,0,6").unwrap();
+        simulator.step(3600.0);
+        let (lon, lat) = simulator.position;
+        assert!((lon + 122.45).abs() < 1e-9, "{lon}");
+        assert!((lat - 37.9).abs() < 1e-3, "{lat}");
+        assert_eq!(simulator.time, 3600.0);
+        // heading east over 180° comes out the other side
+        let mut simulator = Simulator::from_spec("179.99,0,90,10").unwrap();
+        simulator.step(3600.0);
+        assert!(simulator.position.0 < -179.0, "{:?}", simulator.position);
+        assert!(matches!(simulator.sentences()[..], [Sentence::Rmc(_), Sentence::Gga(_), Sentence::Hdt(Some(course))] if course == 90.0));
+    }
+}
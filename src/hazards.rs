@@ -0,0 +1,326 @@
+use gdal::Dataset;
+use gdal::vector::LayerAccess;
+use geo::{Centroid, LineString};
+use log::debug;
+use sfml::graphics::{Color, Font, PrimitiveType, RenderStates, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+
+use crate::attributes::{Attributes, SoundingQuality, WaterLevel, WreckCategory};
+use crate::convert::ConversionStats;
+use crate::depth::{DepthIndex, SafetySettings};
+use crate::geometry::{get_layers, lines_from_geo_geometry, polygons_from_geo_geometry, project_geometry};
+use crate::lights::CHMGD;
+use crate::line::{dash_polyline, tessellate_polyline, LineCap};
+use crate::render::{bearing_arc, get_view_extent, grow_extent, push_convex, Symbology, SoundingLabel, SOUNDING_FONT_SIZE, SOUNDING_SUBSCRIPT_SCALE};
+use crate::text::TextBatch;
+use crate::transform::Projection;
+use crate::units::DepthUnit;
+
+pub const HAZARD_LAYERS: [&str; 3] = ["WRECKS", "OBSTRN", "UWTROC"];
+
+// S-52 takes a non-dangerous wreck of unknown depth to have more water over it than this
+const NON_DANGEROUS_WRECK_DEPTH: f64 = 20.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HazardClass {
+    Wreck,
+    Obstruction,
+    Rock,
+}
+
+impl HazardClass {
+    fn from_layer(name: &str) -> Option<HazardClass> {
+        match name {
+            "WRECKS" => Some(HazardClass::Wreck),
+            "OBSTRN" => Some(HazardClass::Obstruction),
+            "UWTROC" => Some(HazardClass::Rock),
+            _ => None,
+        }
+    }
+}
+
+/// What a hazard is drawn as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HazardSymbol {
+    // ISODGR01, shallower than the safety contour out in water that is otherwise safe
+    IsolatedDanger,
+    // UWTROC03 when always under water with no depth, UWTROC04 when it dries at some state of the tide
+    Rock { uncovers: bool },
+    // WRECKS01 hull when showing, otherwise WRECKS04 and WRECKS05 in a dotted ring when dangerous
+    Wreck { showing: bool, dangerous: bool },
+    // a known depth as a sounding in a dotted ring
+    Sounding(f64),
+    // OBSTRN01, depth unknown
+    Obstruction,
+}
+
+/// One WRECKS, OBSTRN or UWTROC feature
+#[derive(Clone, Debug)]
+pub struct Hazard {
+    pub class: HazardClass,
+    // world position of the symbol, the middle of the feature for lines and areas
+    pub position: Vector2f,
+    // boundaries of areas and the lines of line features in world coordinates, empty for points
+    pub outline: Vec<Vec<Vector2f>>,
+    // VALSOU in meters
+    pub depth: Option<f64>,
+    pub water_level: Option<WaterLevel>,
+    pub category: Option<WreckCategory>,
+    pub quality: Vec<SoundingQuality>,
+    // set once the depth areas are known, see DepthIndex::new
+    pub isolated_danger: bool,
+}
+
+impl Hazard {
+    /// VALSOU unless QUASOU says the depth isn't known
+    pub fn known_depth(&self) -> Option<f64> {
+        let unknown = self.quality.iter().any(|x| matches!(x, SoundingQuality::DepthUnknown | SoundingQuality::LeastDepthUnknown));
+        self.depth.filter(|_| !unknown)
+    }
+
+    /// a VALSOU that hasn't been properly surveyed
+    pub fn is_doubtful(&self) -> bool {
+        self.quality.iter().any(|x| {
+            matches!(x, SoundingQuality::Doubtful | SoundingQuality::Unreliable | SoundingQuality::ReportedNotSurveyed | SoundingQuality::ReportedNotConfirmed)
+        })
+    }
+
+    fn dries(&self) -> bool {
+        matches!(self.water_level, Some(WaterLevel::PartlySubmerged | WaterLevel::AlwaysDry))
+    }
+
+    /// the depth S-52 goes by to decide how dangerous this is (DEPVAL02). Without a VALSOU it comes from
+    /// WATLEV or CATWRK: anything that covers and uncovers or is awash and dangerous wrecks of unknown depth
+    /// are assumed to be right at the surface, and anything else, always submerged rocks included, is taken
+    /// as deep as the depth area around it, `surrounding`
+    pub fn least_depth(&self, surrounding: Option<f64>) -> Option<f64> {
+        if let Some(depth) = self.known_depth() {
+            return Some(depth);
+        }
+        match (self.water_level, self.category) {
+            (Some(WaterLevel::CoversAndUncovers | WaterLevel::Awash), _) => Some(0.0),
+            (_, Some(WreckCategory::NonDangerous)) => Some(NON_DANGEROUS_WRECK_DEPTH),
+            (_, Some(WreckCategory::Dangerous)) => Some(0.0),
+            _ => surrounding,
+        }
+    }
+
+    /// UDWHAZ05: a hazard is an isolated danger when it is shallower than the safety contour but the depth
+    /// area it sits in is deeper, so the safety contour doesn't already keep you off it. Anything that dries
+    /// shows above water and is drawn as itself
+    pub fn is_isolated_danger(&self, surrounding: Option<f64>, safety: &SafetySettings) -> bool {
+        let surrounding_safe = surrounding.is_some_and(|x| safety.is_safe(x));
+        let shallow = self.least_depth(surrounding).is_some_and(|x| !safety.is_safe(x));
+        surrounding_safe && shallow && !self.dries()
+    }
+
+    pub fn symbol(&self) -> HazardSymbol {
+        if self.isolated_danger {
+            return HazardSymbol::IsolatedDanger;
+        }
+        match self.class {
+            HazardClass::Rock => {
+                let uncovers = self.dries() || matches!(self.water_level, Some(WaterLevel::CoversAndUncovers | WaterLevel::Awash));
+                match self.known_depth() {
+                    Some(depth) if !uncovers => HazardSymbol::Sounding(depth),
+                    _ => HazardSymbol::Rock { uncovers },
+                }
+            }
+            HazardClass::Wreck => {
+                let showing = self.dries() || matches!(self.category, Some(WreckCategory::MastShowing | WreckCategory::HullShowing));
+                match self.known_depth() {
+                    Some(depth) if !showing => HazardSymbol::Sounding(depth),
+                    _ => HazardSymbol::Wreck { showing, dangerous: self.category == Some(WreckCategory::Dangerous) },
+                }
+            }
+            HazardClass::Obstruction => self.known_depth().map_or(HazardSymbol::Obstruction, HazardSymbol::Sounding),
+        }
+    }
+
+    /// for the cursor readout, e.g. "dangerous wreck 2.1 m (doubtful)"
+    pub fn describe(&self, unit: DepthUnit) -> String {
+        let name = match (self.class, self.category) {
+            (HazardClass::Wreck, Some(WreckCategory::NonDangerous)) => "non-dangerous wreck",
+            (HazardClass::Wreck, Some(WreckCategory::Dangerous)) => "dangerous wreck",
+            (HazardClass::Wreck, Some(WreckCategory::DistributedRemains)) => "wreck remains",
+            (HazardClass::Wreck, Some(WreckCategory::MastShowing)) => "wreck showing mast",
+            (HazardClass::Wreck, Some(WreckCategory::HullShowing)) => "wreck showing hull",
+            (HazardClass::Wreck, None) => "wreck",
+            (HazardClass::Obstruction, _) => "obstruction",
+            (HazardClass::Rock, _) => "rock",
+        };
+        let mut parts = vec![if self.isolated_danger { format!("isolated danger, {name}") } else { name.to_string() }];
+        match (self.known_depth(), self.water_level) {
+            (Some(depth), _) => parts.push(unit.format(depth)),
+            (None, Some(WaterLevel::CoversAndUncovers)) => parts.push("covers and uncovers".to_string()),
+            (None, Some(WaterLevel::Awash)) => parts.push("awash".to_string()),
+            (None, Some(WaterLevel::AlwaysDry | WaterLevel::PartlySubmerged)) => parts.push("dries".to_string()),
+            (None, _) => parts.push("depth unknown".to_string()),
+        }
+        if self.is_doubtful() {
+            parts.push("(doubtful)".to_string());
+        }
+        parts.join(" ")
+    }
+}
+
+fn world_line(line: &LineString) -> Vec<Vector2f> {
+    line.coords().map(|x| Vector2f::new(x.x as f32, x.y as f32)).collect()
+}
+
+/// reads the wrecks, obstructions and underwater rocks of a dataset, they can be points, lines or areas
+pub fn get_hazards(ds: &Dataset, projection: &dyn Projection) -> Vec<Hazard> {
+    let mut hazards = Vec::new();
+    for mut layer in get_layers(ds, HAZARD_LAYERS.to_vec()) {
+        let layer_name = layer.name();
+        let class = match HazardClass::from_layer(&layer_name) {
+            Some(class) => class,
+            None => continue,
+        };
+        let mut stats = ConversionStats::default();
+        for feature in layer.features() {
+            let geometry = match stats.convert(feature.geometry()) {
+                Some(geometry) => project_geometry(geometry, projection),
+                None => continue,
+            };
+            let position = match geometry.centroid() {
+                Some(point) => Vector2f::new(point.x() as f32, point.y() as f32),
+                None => {
+                    stats.skipped += 1;
+                    continue;
+                }
+            };
+            let mut outline: Vec<Vec<Vector2f>> = polygons_from_geo_geometry(geometry.clone())
+                .iter()
+                .flat_map(|x| std::iter::once(x.exterior()).chain(x.interiors()).map(world_line).collect::<Vec<_>>())
+                .collect();
+            outline.extend(lines_from_geo_geometry(geometry).iter().map(world_line));
+            let attributes = Attributes::new(&feature);
+            hazards.push(Hazard {
+                class,
+                position,
+                outline,
+                depth: attributes.float("VALSOU"),
+                water_level: attributes.enumeration(),
+                category: attributes.enumeration(),
+                quality: attributes.list(),
+                isolated_danger: false,
+            });
+        }
+        stats.report(&layer_name);
+    }
+    debug!("Found {} wrecks, obstructions and rocks", hazards.len());
+    hazards
+}
+
+// hazard symbol sizes are in pixels
+const HAZARD_RING_RADIUS: f32 = 10.0;
+const HAZARD_RING_DOTS: [f32; 2] = [0.5, 3.0];
+const HAZARD_BOUNDARY_DASH: [f32; 2] = [5.0, 3.0];
+const ISOLATED_DANGER_RADIUS: f32 = 8.0;
+
+fn push_dot(out: &mut Vec<Vertex>, center: Vector2f, radius: f32, color: Color) {
+    push_convex(out, &bearing_arc(center, 0.0, 360.0, radius), color);
+}
+
+/// Wrecks, obstructions and rocks with the S-52 symbols. Ones shallower than the safety contour in otherwise
+/// safe water get the magenta isolated danger symbol whatever they are, the rest are drawn as themselves
+/// with their depth in a dotted ring when it's known. Lines and areas also get a dashed boundary.
+pub fn render_hazards(window: &mut dyn RenderTarget, index: &DepthIndex, font: &Font, zoom: f32, view: &View, safety_depth: f64, unit: DepthUnit) {
+    if !Symbology::Hazards.shown(zoom) {
+        return;
+    }
+    let extent = grow_extent(&get_view_extent(view), HAZARD_RING_RADIUS * zoom);
+    let dots = HAZARD_RING_DOTS.map(|x| x * zoom);
+    let dash = HAZARD_BOUNDARY_DASH.map(|x| x * zoom);
+    let mut vertices = Vec::new();
+    let mut depths = TextBatch::new(font, SOUNDING_FONT_SIZE);
+    let dotted_ring = |out: &mut Vec<Vertex>, center: Vector2f| {
+        for piece in dash_polyline(&bearing_arc(center, 0.0, 360.0, HAZARD_RING_RADIUS * zoom), &dots) {
+            tessellate_polyline(&piece, 0.75 * zoom, LineCap::Round, Color::BLACK, out);
+        }
+    };
+    for hazard in index.hazards_in(&extent) {
+        let p = hazard.position;
+        let at = |x: f32, y: f32| p + Vector2f::new(x, y) * zoom;
+        let symbol = hazard.symbol();
+        let boundary_color = if symbol == HazardSymbol::IsolatedDanger { CHMGD } else { Color::BLACK };
+        for line in &hazard.outline {
+            for piece in dash_polyline(line, &dash) {
+                tessellate_polyline(&piece, 0.5 * zoom, LineCap::Butt, boundary_color, &mut vertices);
+            }
+        }
+        match symbol {
+            HazardSymbol::IsolatedDanger => {
+                push_dot(&mut vertices, p, ISOLATED_DANGER_RADIUS * zoom, Color::WHITE);
+                let mut ring = bearing_arc(p, 0.0, 360.0, ISOLATED_DANGER_RADIUS * zoom);
+                ring.push(ring[0]);
+                tessellate_polyline(&ring, zoom, LineCap::Butt, CHMGD, &mut vertices);
+                let arm = ISOLATED_DANGER_RADIUS * std::f32::consts::FRAC_1_SQRT_2;
+                tessellate_polyline(&[at(-arm, -arm), at(arm, arm)], zoom, LineCap::Butt, CHMGD, &mut vertices);
+                tessellate_polyline(&[at(-arm, arm), at(arm, -arm)], zoom, LineCap::Butt, CHMGD, &mut vertices);
+            }
+            HazardSymbol::Rock { uncovers } => {
+                tessellate_polyline(&[at(-5.0, 0.0), at(5.0, 0.0)], 0.75 * zoom, LineCap::Butt, Color::BLACK, &mut vertices);
+                tessellate_polyline(&[at(0.0, -5.0), at(0.0, 5.0)], 0.75 * zoom, LineCap::Butt, Color::BLACK, &mut vertices);
+                if uncovers {
+                    // the dots between the arms make it the asterisk of a rock that dries
+                    for (x, y) in [(-3.0, -3.0), (3.0, -3.0), (-3.0, 3.0), (3.0, 3.0)] {
+                        push_dot(&mut vertices, at(x, y), 1.2 * zoom, Color::BLACK);
+                    }
+                }
+            }
+            HazardSymbol::Wreck { showing: true, .. } => {
+                push_convex(&mut vertices, &[at(-7.0, 0.0), at(7.0, 0.0), at(4.5, 3.5), at(-4.5, 3.5)], Color::BLACK);
+                tessellate_polyline(&[at(0.0, 0.0), at(0.0, -6.0)], 0.75 * zoom, LineCap::Butt, Color::BLACK, &mut vertices);
+            }
+            HazardSymbol::Wreck { showing: false, dangerous } => {
+                tessellate_polyline(&[at(-7.0, 0.0), at(7.0, 0.0)], 0.75 * zoom, LineCap::Butt, Color::BLACK, &mut vertices);
+                for x in [-3.5, 0.0, 3.5] {
+                    tessellate_polyline(&[at(x, -3.0), at(x, 3.0)], 0.75 * zoom, LineCap::Butt, Color::BLACK, &mut vertices);
+                }
+                if dangerous {
+                    dotted_ring(&mut vertices, p);
+                }
+            }
+            HazardSymbol::Sounding(depth) => {
+                dotted_ring(&mut vertices, p);
+                if Symbology::Hazards.labelled(zoom) {
+                    // smaller than a sounding so it fits in the ring
+                    SoundingLabel::new(&depths, depth, unit, p, zoom * SOUNDING_SUBSCRIPT_SCALE, safety_depth).add_to(&mut depths);
+                }
+            }
+            HazardSymbol::Obstruction => dotted_ring(&mut vertices, p),
+        }
+    }
+    window.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+    depths.draw(window);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hazard(class: HazardClass, depth: Option<f64>, water_level: Option<WaterLevel>) -> Hazard {
+        Hazard { class, position: Vector2f::new(0.0, 0.0), outline: Vec::new(), depth, water_level, category: None, quality: Vec::new(), isolated_danger: false }
+    }
+
+    #[test]
+    fn rock_symbols() {
+        assert_eq!(hazard(HazardClass::Rock, Some(3.2), Some(WaterLevel::AlwaysSubmerged)).symbol(), HazardSymbol::Sounding(3.2));
+        assert_eq!(hazard(HazardClass::Rock, Some(3.2), None).symbol(), HazardSymbol::Sounding(3.2));
+        assert_eq!(hazard(HazardClass::Rock, None, Some(WaterLevel::AlwaysSubmerged)).symbol(), HazardSymbol::Rock { uncovers: false });
+        // a rock that dries is drawn as one whatever its VALSOU
+        assert_eq!(hazard(HazardClass::Rock, Some(0.5), Some(WaterLevel::CoversAndUncovers)).symbol(), HazardSymbol::Rock { uncovers: true });
+        let mut unsurveyed = hazard(HazardClass::Rock, Some(3.2), Some(WaterLevel::AlwaysSubmerged));
+        unsurveyed.quality = vec![SoundingQuality::DepthUnknown];
+        assert_eq!(unsurveyed.symbol(), HazardSymbol::Rock { uncovers: false });
+    }
+
+    #[test]
+    fn isolated_danger_wins() {
+        let mut rock = hazard(HazardClass::Rock, Some(1.0), Some(WaterLevel::AlwaysSubmerged));
+        rock.isolated_danger = true;
+        assert_eq!(rock.symbol(), HazardSymbol::IsolatedDanger);
+    }
+}
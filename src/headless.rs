@@ -0,0 +1,195 @@
+use sfml::graphics::RenderTexture;
+use log::info;
+
+use crate::chart::ChartSet;
+use crate::geometry::LayerExtent;
+use crate::render::{get_depare_extent, get_view_for_extent, get_world_per_pixel, render_chart_set, render_scale_bar};
+use crate::transform::Projection;
+
+pub const RENDER_USAGE: &str = "usage: chartplotter render [--bbox MINLON,MINLAT,MAXLON,MAXLAT] [--size WIDTHxHEIGHT] [--soundings] -o OUT.png";
+
+/// Options for `chartplotter render`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderArgs {
+    // min lon, min lat, max lon, max lat - None fits every chart
+    pub bbox: Option<(f64, f64, f64, f64)>,
+    pub size: (u32, u32),
+    pub output: String,
+    pub render_depth: bool,
+}
+
+/// parses everything after the `render` subcommand
+pub fn parse_render_args(args: &[String]) -> Result<RenderArgs, String> {
+    let mut bbox = None;
+    let mut size = (2048, 2048);
+    let mut output = None;
+    let mut render_depth = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bbox" => {
+                let value = args.next().ok_or("--bbox needs a value")?;
+                bbox = Some(parse_bbox(value)?);
+            }
+            "--size" => {
+                let value = args.next().ok_or("--size needs a value")?;
+                size = parse_size(value)?;
+            }
+            "-o" | "--output" => {
+                let value = args.next().ok_or("-o needs a value")?;
+                output = Some(value.clone());
+            }
+            "--soundings" => render_depth = true,
+            unknown => return Err(format!("unknown argument '{unknown}'")),
+        }
+    }
+    let output = output.ok_or("missing output file, pass -o OUT.png")?;
+    Ok(RenderArgs { bbox, size, output, render_depth })
+}
+
+fn parse_bbox(value: &str) -> Result<(f64, f64, f64, f64), String> {
+    let parts: Result<Vec<f64>, _> = value.split(',').map(|x| x.trim().parse::<f64>()).collect();
+    match parts {
+        Ok(p) if p.len() == 4 && p[0] < p[2] && p[1] < p[3] => Ok((p[0], p[1], p[2], p[3])),
+        _ => Err(format!("bad --bbox '{value}', expected MINLON,MINLAT,MAXLON,MAXLAT")),
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let parsed = value.split_once('x').and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+    match parsed {
+        Some((w, h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("bad --size '{value}', expected WIDTHxHEIGHT")),
+    }
+}
+
+/// renders the chart set offscreen and writes it out as a png
+pub fn render_to_png(charts: &ChartSet, args: &RenderArgs) -> Result<(), String> {
+    let extent = match args.bbox {
+        Some((min_lon, min_lat, max_lon, max_lat)) => {
+            // north is up so the max latitude ends up with the smaller y
+            let (min_x, min_y) = charts.projection.forward((min_lon, max_lat));
+            let (max_x, max_y) = charts.projection.forward((max_lon, min_lat));
+            LayerExtent { MinX: min_x as f32, MaxX: max_x as f32, MinY: min_y as f32, MaxY: max_y as f32 }
+        }
+        None => get_depare_extent(&charts.depare_map),
+    };
+    let view = get_view_for_extent(&extent, args.size);
+    let zoom = get_world_per_pixel(&view, args.size);
+    info!("Rendering {:?} at {:?} to {}", extent, args.size, args.output);
+
+    let mut texture = RenderTexture::new(args.size.0, args.size.1).ok_or("couldn't create an offscreen render texture")?;
+    render_chart_set(&mut texture, charts, &view, zoom, args.render_depth);
+    render_scale_bar(&mut texture, &charts.projection, &view, zoom, &charts.units, &charts.font);
+    texture.display();
+    let image = texture.texture().copy_to_image().ok_or("couldn't copy the render texture to an image")?;
+    if !image.save_to_file(&args.output) {
+        return Err(format!("couldn't write {}", args.output));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use sfml::graphics::Image;
+
+    use super::*;
+    use crate::chart::load_chart_set;
+    use crate::config::{Config, DEFAULT_CONFIG_PATH};
+
+    // the bundled cell rendered with config.toml, regenerate it with UPDATE_GOLDEN=1 after a change that's meant to show
+    const GOLDEN_PATH: &str = "tests/golden/BYU_CHICO.png";
+    const GOLDEN_OUTPUT: &str = "target/golden/BYU_CHICO.png";
+    // a channel can be off by this much before the pixel counts as changed, drivers antialias a little differently
+    const CHANNEL_TOLERANCE: u8 = 16;
+    // and this fraction of the pixels can change before the render counts as different
+    const CHANGED_PIXEL_TOLERANCE: f64 = 0.002;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn render_args() {
+        assert_eq!(
+            parse_render_args(&args(&["-o", "out.png"])),
+            Ok(RenderArgs { bbox: None, size: (2048, 2048), output: "out.png".to_string(), render_depth: false })
+        );
+        assert_eq!(
+            parse_render_args(&args(&["--bbox", "-117.3,32.6,-117.1,32.8", "--size", "800x600", "--soundings", "--output", "out.png"])),
+            Ok(RenderArgs { bbox: Some((-117.3, 32.6, -117.1, 32.8)), size: (800, 600), output: "out.png".to_string(), render_depth: true })
+        );
+    }
+
+    #[test]
+    fn bad_render_args() {
+        for bad in [
+            &[][..],
+            &["--soundings"],
+            &["-o"],
+            &["-o", "out.png", "--bbox"],
+            &["-o", "out.png", "--size"],
+            &["-o", "out.png", "--scale", "2"],
+            &["-o", "out.png", "--bbox", "1,2,3"],
+            &["-o", "out.png", "--size", "800"],
+        ] {
+            assert!(parse_render_args(&args(bad)).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn bbox() {
+        assert_eq!(parse_bbox("-117.3, 32.6, -117.1, 32.8"), Ok((-117.3, 32.6, -117.1, 32.8)));
+        // min has to be less than max both ways
+        assert!(parse_bbox("-117.1,32.6,-117.3,32.8").is_err());
+        assert!(parse_bbox("-117.3,32.8,-117.1,32.6").is_err());
+        assert!(parse_bbox("-117.3,32.6,-117.3,32.8").is_err());
+        assert!(parse_bbox("-117.3,32.6,-117.1").is_err());
+        assert!(parse_bbox("-117.3,32.6,-117.1,32.8,1").is_err());
+        assert!(parse_bbox("west,32.6,-117.1,32.8").is_err());
+        assert!(parse_bbox("").is_err());
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("800x600"), Ok((800, 600)));
+        assert_eq!(parse_size("1x1"), Ok((1, 1)));
+        assert!(parse_size("0x600").is_err());
+        assert!(parse_size("800x0").is_err());
+        assert!(parse_size("800").is_err());
+        assert!(parse_size("800X600").is_err());
+        assert!(parse_size("-800x600").is_err());
+        assert!(parse_size("800x600x2").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs GDAL and an OpenGL context, run with cargo test -- --ignored"]
+    fn golden_image() {
+        let config = Config::load(DEFAULT_CONFIG_PATH).unwrap();
+        assert!(config.validate().is_empty());
+        let charts = load_chart_set(&config);
+        std::fs::create_dir_all(Path::new(GOLDEN_OUTPUT).parent().unwrap()).unwrap();
+        let args = RenderArgs { bbox: None, size: (1024, 1024), output: GOLDEN_OUTPUT.to_string(), render_depth: true };
+        render_to_png(&charts, &args).unwrap();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(Path::new(GOLDEN_PATH).parent().unwrap()).unwrap();
+            std::fs::copy(GOLDEN_OUTPUT, GOLDEN_PATH).unwrap();
+            return;
+        }
+        let expected = Image::from_file(GOLDEN_PATH).unwrap_or_else(|| panic!("no {GOLDEN_PATH}, make one with UPDATE_GOLDEN=1"));
+        let actual = Image::from_file(GOLDEN_OUTPUT).unwrap();
+        assert_eq!(actual.size(), expected.size());
+        let changed = actual
+            .pixel_data()
+            .chunks(4)
+            .zip(expected.pixel_data().chunks(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+            .count();
+        let fraction = changed as f64 / (args.size.0 * args.size.1) as f64;
+        assert!(fraction <= CHANGED_PIXEL_TOLERANCE, "{:.2}% of the pixels changed, compare {GOLDEN_OUTPUT} with {GOLDEN_PATH}", fraction * 100.0);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use gdal::vector::{Layer, LayerAccess};
+use log::{debug, warn};
+use sfml::graphics::{Color, Font, PrimitiveType, RenderStates, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+
+use crate::attributes::{AttributeEnum, Attributes, Colour, LightCharacter};
+use crate::line::{dash_polyline, tessellate_polyline, LineCap};
+use crate::render::{bearing_arc, bearing_direction, get_view_extent, visible, Symbology};
+use crate::text::TextBatch;
+use crate::transform::Projection;
+
+// S-52 light colours, white lights are shown yellow like on a paper chart
+pub const LITRD: Color = Color::rgb(237, 28, 36);
+pub const LITGN: Color = Color::rgb(0, 166, 81);
+pub const LITYW: Color = Color::rgb(255, 214, 0);
+// anything else, e.g. blue or violet
+pub const CHMGD: Color = Color::rgb(197, 69, 195);
+
+// sector colours are written in this order in a light's label, any others after them
+const LABEL_COLOUR_ORDER: [Colour; 3] = [Colour::White, Colour::Red, Colour::Green];
+
+/// One LIGHTS feature
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub colours: Vec<Colour>,
+    // SECTR1, SECTR2 in degrees true, bearings seen from seaward looking toward the light
+    pub sector: Option<(f64, f64)>,
+    // VALNMR, nominal range in nautical miles
+    pub range: Option<f64>,
+    // LITCHR
+    pub character: Option<LightCharacter>,
+    // SIGGRP, flashes in a group like "(2)" or "(2+1)"
+    pub group: Option<String>,
+    // SIGPER, period in seconds
+    pub period: Option<f64>,
+    // ORIENT, direction of the beam of a directional light in degrees true
+    pub orient: Option<f64>,
+}
+
+impl Light {
+    pub fn color(&self) -> Color {
+        // a sector only has one colour, for a multi colour light going by the first is as good as any
+        match self.colours.first() {
+            Some(Colour::White | Colour::Yellow | Colour::Amber | Colour::Orange) => LITYW,
+            Some(Colour::Red) => LITRD,
+            Some(Colour::Green) => LITGN,
+            _ => CHMGD,
+        }
+    }
+}
+
+/// All the lights on one structure, a sectored light is one LIGHTS feature per sector
+#[derive(Clone, Debug)]
+pub struct LightStation {
+    // world position
+    pub position: Vector2f,
+    pub lights: Vec<Light>,
+}
+
+// what a station's label says about each rhythm its lights show: colours and range
+type Description = ((String, String), Vec<Colour>, Option<f64>);
+
+impl LightStation {
+    /// the chart label, e.g. "Fl(2) G 6s 5M". Sectors that only differ in colour share one description like "Fl WRG 4s 9M"
+    pub fn label(&self) -> String {
+        let mut descriptions: Vec<Description> = Vec::new();
+        for light in &self.lights {
+            let rhythm = rhythm(light);
+            match descriptions.iter_mut().find(|x| x.0 == rhythm) {
+                Some((_, colours, range)) => {
+                    for colour in &light.colours {
+                        if !colours.contains(colour) {
+                            colours.push(*colour);
+                        }
+                    }
+                    // with different ranges per colour the chart gives the longest
+                    *range = match (*range, light.range) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => descriptions.push((rhythm, light.colours.clone(), light.range)),
+            }
+        }
+        descriptions
+            .into_iter()
+            .map(|((character, period), mut colours, range)| {
+                // chart order is white, red, green then the rest by their COLOUR code
+                colours.sort_by_key(|x| (LABEL_COLOUR_ORDER.iter().position(|y| y == x).unwrap_or(LABEL_COLOUR_ORDER.len()), x.code()));
+                let mut parts = vec![character, colours.iter().map(|x| colour_abbreviation(*x)).collect(), period];
+                if let Some(range) = range {
+                    parts.push(format!("{}M", trim_number(range)));
+                }
+                parts.retain(|x| !x.is_empty());
+                parts.join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+/// characteristic with its group and the period, e.g. ("Fl(2)", "6s"), everything in a label but the colours and range
+fn rhythm(light: &Light) -> (String, String) {
+    let mut character = light.character.map_or(String::new(), |x| character_abbreviation(x).to_string());
+    if let Some(group) = &light.group {
+        // "()" and "(1)" are a single flash, which goes without saying
+        if group != "()" && group != "(1)" && !character.is_empty() {
+            character.push_str(group);
+        }
+    }
+    let period = light.period.map_or(String::new(), |x| format!("{}s", trim_number(x)));
+    (character, period)
+}
+
+/// 6 rather than 6.0 but 2.5 stays 2.5
+fn trim_number(value: f64) -> String {
+    if value.fract() == 0.0 { format!("{value:.0}") } else { format!("{value}") }
+}
+
+/// LITCHR abbreviations as printed on charts
+fn character_abbreviation(character: LightCharacter) -> &'static str {
+    use LightCharacter::*;
+    match character {
+        Fixed => "F",
+        Flashing => "Fl",
+        LongFlashing => "LFl",
+        QuickFlashing => "Q",
+        VeryQuickFlashing => "VQ",
+        UltraQuickFlashing => "UQ",
+        Isophased => "Iso",
+        Occulting => "Oc",
+        InterruptedQuickFlashing => "IQ",
+        InterruptedVeryQuickFlashing => "IVQ",
+        InterruptedUltraQuickFlashing => "IUQ",
+        Morse => "Mo",
+        FixedAndFlashing => "FFl",
+        FlashAndLongFlash => "Fl+LFl",
+        OccultingAndFlash => "Oc+Fl",
+        FixedAndLongFlash => "FLFl",
+        AlternatingOcculting => "Al.Oc",
+        AlternatingLongFlashing => "Al.LFl",
+        AlternatingFlashing => "Al.Fl",
+        AlternatingGroup => "Al.Gr",
+        QuickFlashPlusLongFlash => "Q+LFl",
+        VeryQuickFlashPlusLongFlash => "VQ+LFl",
+        UltraQuickFlashPlusLongFlash => "UQ+LFl",
+        Alternating => "Al",
+        FixedAndAlternatingFlashing => "Al.FFl",
+    }
+}
+
+/// COLOUR abbreviations for lights
+fn colour_abbreviation(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "W",
+        Colour::Red => "R",
+        Colour::Green => "G",
+        Colour::Blue => "Bu",
+        Colour::Yellow => "Y",
+        Colour::Amber => "Am",
+        Colour::Violet => "Vi",
+        Colour::Orange => "Or",
+        _ => "",
+    }
+}
+
+/// reads a LIGHTS layer, lights at the same spot are put together on one station
+pub fn get_lights(layer: &mut Layer, projection: &dyn Projection) -> Vec<LightStation> {
+    let mut stations: Vec<LightStation> = Vec::new();
+    let mut by_position: HashMap<(u64, u64), usize> = HashMap::new();
+    for feature in layer.features() {
+        let point = match feature.geometry().and_then(|x| x.get_point_vec().pop()) {
+            Some(point) => point,
+            None => {
+                warn!("No point found in LIGHTS layer!");
+                continue;
+            }
+        };
+        let (x, y) = projection.forward((point.0, point.1));
+        let attributes = Attributes::new(&feature);
+        let light = Light {
+            colours: attributes.list(),
+            sector: attributes.float("SECTR1").zip(attributes.float("SECTR2")),
+            range: attributes.float("VALNMR"),
+            character: attributes.enumeration(),
+            group: attributes.string("SIGGRP"),
+            period: attributes.float("SIGPER"),
+            orient: attributes.float("ORIENT"),
+        };
+        let key = (x.to_bits(), y.to_bits());
+        match by_position.get(&key) {
+            Some(i) => stations[*i].lights.push(light),
+            None => {
+                by_position.insert(key, stations.len());
+                stations.push(LightStation { position: Vector2f::new(x as f32, y as f32), lights: vec![light] });
+            }
+        }
+    }
+    debug!("Found {} light stations", stations.len());
+    stations
+}
+
+// light symbol sizes are in pixels
+const LIGHT_FLARE_LENGTH: f32 = 18.0;
+const LIGHT_FLARE_RADIUS: f32 = 4.0;
+// the first flare on a light points this way in degrees true, like on a paper chart
+const LIGHT_FLARE_BEARING: f64 = 135.0;
+const LIGHT_SECTOR_RADIUS: f32 = 50.0;
+const LIGHT_LEG_LENGTH: f32 = 75.0;
+const LIGHT_LEG_DASH: [f32; 2] = [6.0, 4.0];
+const LIGHT_LABEL_SIZE: u32 = 12;
+
+/// a teardrop pointing away from the light as a triangle fan, `grow` makes it bigger all round for an outline
+fn push_flare(out: &mut Vec<Vertex>, position: Vector2f, bearing: f64, zoom: f32, grow: f32, color: Color) {
+    let radius = (LIGHT_FLARE_RADIUS + grow) * zoom;
+    let center_distance = (LIGHT_FLARE_LENGTH - LIGHT_FLARE_RADIUS) * zoom;
+    let tip = position - bearing_direction(bearing) * (grow * zoom);
+    let center = position + bearing_direction(bearing) * center_distance;
+    // the round end is the part of the circle the tangents from the tip don't cut off
+    let spread = 90.0 + (radius / (center_distance + grow * zoom)).asin().to_degrees() as f64;
+    let outline = bearing_arc(center, bearing - spread, bearing + spread, radius);
+    for pair in outline.windows(2) {
+        out.push(Vertex::with_pos_color(tip, color));
+        out.push(Vertex::with_pos_color(pair[0], color));
+        out.push(Vertex::with_pos_color(pair[1], color));
+    }
+}
+
+/// S-52 style lights: sectors as coloured arcs between dashed legs, all round lights as a flare
+/// and the characteristic written beside the light, e.g. "Fl(2) G 6s 5M"
+pub fn render_lights(window: &mut dyn RenderTarget, stations: &[LightStation], font: &Font, zoom: f32, view: &View) {
+    if !Symbology::Lights.shown(zoom) {
+        return;
+    }
+    let view = get_view_extent(view);
+    let margin = LIGHT_LEG_LENGTH * zoom;
+    let dash = LIGHT_LEG_DASH.map(|x| x * zoom);
+    let mut lines = Vec::new();
+    let mut flares = Vec::new();
+    let mut labels = TextBatch::new(font, LIGHT_LABEL_SIZE);
+    for station in stations {
+        let p = station.position;
+        if !visible(p, margin, &view) {
+            continue;
+        }
+        let mut flare_bearing = LIGHT_FLARE_BEARING;
+        for light in &station.lights {
+            // sector limits and ORIENT are bearings from seaward, toward the light, so they're drawn going the other way
+            let legs = match (light.sector, light.orient) {
+                (Some(sector), _) => vec![sector.0 + 180.0, sector.1 + 180.0],
+                (None, Some(orient)) => vec![orient + 180.0],
+                (None, None) => Vec::new(),
+            };
+            for bearing in legs {
+                let leg = [p, p + bearing_direction(bearing) * (LIGHT_LEG_LENGTH * zoom)];
+                for piece in dash_polyline(&leg, &dash) {
+                    tessellate_polyline(&piece, 0.5 * zoom, LineCap::Butt, Color::BLACK, &mut lines);
+                }
+            }
+            match light.sector {
+                Some((start, end)) => {
+                    let arc = bearing_arc(p, start + 180.0, end + 180.0, LIGHT_SECTOR_RADIUS * zoom);
+                    tessellate_polyline(&arc, 3.0 * zoom, LineCap::Butt, Color::BLACK, &mut lines);
+                    tessellate_polyline(&arc, 2.0 * zoom, LineCap::Butt, light.color(), &mut lines);
+                }
+                None => {
+                    push_flare(&mut flares, p, flare_bearing, zoom, 1.0, Color::BLACK);
+                    push_flare(&mut flares, p, flare_bearing, zoom, 0.0, light.color());
+                    // more than one all round light on a station fans out so each flare shows
+                    flare_bearing -= 90.0;
+                }
+            }
+        }
+        if Symbology::Lights.labelled(zoom) {
+            let label = station.label();
+            let bounds = labels.measure(&label, zoom, false);
+            let position = Vector2f::new(p.x + LIGHT_FLARE_LENGTH * zoom - bounds.left, p.y - bounds.height / 2.0 - bounds.top);
+            labels.add(&label, position, zoom, Color::BLACK, false);
+        }
+    }
+    window.draw_primitives(&lines, PrimitiveType::TRIANGLES, &RenderStates::default());
+    window.draw_primitives(&flares, PrimitiveType::TRIANGLES, &RenderStates::default());
+    labels.draw(window);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light(colours: &[Colour], character: LightCharacter, group: Option<&str>, period: Option<f64>, range: Option<f64>) -> Light {
+        Light { colours: colours.to_vec(), sector: None, range, character: Some(character), group: group.map(|x| x.to_string()), period, orient: None }
+    }
+
+    fn label(lights: Vec<Light>) -> String {
+        LightStation { position: Vector2f::new(0.0, 0.0), lights }.label()
+    }
+
+    #[test]
+    fn single_light() {
+        assert_eq!(label(vec![light(&[Colour::Green], LightCharacter::Flashing, Some("(2)"), Some(6.0), Some(5.0))]), "Fl(2) G 6s 5M");
+        assert_eq!(label(vec![light(&[Colour::Red], LightCharacter::Flashing, Some("(1)"), Some(2.5), None)]), "Fl R 2.5s");
+    }
+
+    #[test]
+    fn fixed_light_with_empty_group() {
+        assert_eq!(label(vec![light(&[Colour::White], LightCharacter::Fixed, Some(""), None, Some(3.0))]), "F W 3M");
+        assert_eq!(label(vec![light(&[Colour::Red], LightCharacter::Fixed, Some("()"), None, None)]), "F R");
+    }
+
+    #[test]
+    fn sectors_share_a_description() {
+        // one LIGHTS feature per sector, listed in the order they were charted
+        let sectors = vec![
+            light(&[Colour::Green], LightCharacter::Flashing, Some("(2)"), Some(6.0), Some(4.0)),
+            light(&[Colour::White], LightCharacter::Flashing, Some("(2)"), Some(6.0), Some(6.0)),
+            light(&[Colour::Red], LightCharacter::Flashing, Some("(2)"), Some(6.0), Some(4.0)),
+        ];
+        assert_eq!(label(sectors), "Fl(2) WRG 6s 6M");
+    }
+
+    #[test]
+    fn different_rhythms() {
+        let lights = vec![
+            light(&[Colour::White], LightCharacter::Flashing, None, Some(10.0), Some(18.0)),
+            light(&[Colour::Red], LightCharacter::Fixed, None, None, Some(12.0)),
+        ];
+        assert_eq!(label(lights), "Fl W 10s 18M / F R 12M");
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+
+use sfml::graphics::{Color, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+use sfml::SfBox;
+
+use crate::geometry::{LayerExtent, Plotable};
+use crate::render::is_extent_in_view;
+use crate::tiles::TiledVertices;
+
+// how many triangles make up half a circle on a round cap
+const ROUND_CAP_SEGMENTS: usize = 8;
+// rebuild the cached triangles once the zoom has drifted this far from when they were made
+const RETESSELLATE_RATIO: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+impl LineCap {
+    pub fn from_name(name: &str) -> Option<LineCap> {
+        match name.to_lowercase().as_str() {
+            "butt" => Some(LineCap::Butt),
+            "square" => Some(LineCap::Square),
+            "round" => Some(LineCap::Round),
+            _ => None,
+        }
+    }
+}
+
+/// width and dash lengths are in screen pixels so lines look the same at every zoom
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineStyle {
+    pub color: Color,
+    pub width: f32,
+    pub dash: Vec<f32>,
+    pub cap: LineCap,
+}
+
+impl LineStyle {
+    pub fn solid(color: Color, width: f32) -> LineStyle {
+        LineStyle { color, width, dash: Vec::new(), cap: LineCap::Butt }
+    }
+}
+
+pub fn length(v: Vector2f) -> f32 {
+    (v.x * v.x + v.y * v.y).sqrt()
+}
+
+fn normalize(v: Vector2f) -> Vector2f {
+    let len = length(v);
+    if len == 0.0 {
+        return Vector2f::new(0.0, 0.0);
+    }
+    v / len
+}
+
+/// cuts a polyline up into its "on" pieces following a pattern of on, off, on, off, ... lengths
+pub fn dash_polyline(points: &[Vector2f], pattern: &[f32]) -> Vec<Vec<Vector2f>> {
+    if pattern.is_empty() || pattern.iter().sum::<f32>() <= 0.0 || points.len() < 2 {
+        return vec![points.to_vec()];
+    }
+    let mut dashes = Vec::new();
+    let mut current = vec![points[0]];
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    for pair in points.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let segment_length = length(end - start);
+        let direction = normalize(end - start);
+        let mut travelled = 0.0;
+        while segment_length - travelled > remaining {
+            travelled += remaining;
+            let split = start + direction * travelled;
+            if index % 2 == 0 {
+                // end of a dash
+                current.push(split);
+                dashes.push(std::mem::take(&mut current));
+            }
+            else {
+                // start of a dash
+                current = vec![split];
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= segment_length - travelled;
+        if index % 2 == 0 {
+            current.push(end);
+        }
+    }
+    if index % 2 == 0 && current.len() >= 2 {
+        dashes.push(current);
+    }
+    dashes
+}
+
+fn push_triangle(out: &mut Vec<Vertex>, a: Vector2f, b: Vector2f, c: Vector2f, color: Color) {
+    out.push(Vertex::with_pos_color(a, color));
+    out.push(Vertex::with_pos_color(b, color));
+    out.push(Vertex::with_pos_color(c, color));
+}
+
+fn push_round_cap(out: &mut Vec<Vertex>, center: Vector2f, direction: Vector2f, half_width: f32, color: Color) {
+    // fans out half a circle on the side the direction points to
+    let start_angle = direction.y.atan2(direction.x) - PI / 2.0;
+    for i in 0..ROUND_CAP_SEGMENTS {
+        let a0 = start_angle + PI * i as f32 / ROUND_CAP_SEGMENTS as f32;
+        let a1 = start_angle + PI * (i + 1) as f32 / ROUND_CAP_SEGMENTS as f32;
+        let p0 = center + Vector2f::new(a0.cos(), a0.sin()) * half_width;
+        let p1 = center + Vector2f::new(a1.cos(), a1.sin()) * half_width;
+        push_triangle(out, center, p0, p1, color);
+    }
+}
+
+/// turns a polyline into a triangle strip, written out as a plain triangle list so many lines can share a draw call
+pub fn tessellate_polyline(points: &[Vector2f], half_width: f32, cap: LineCap, color: Color, out: &mut Vec<Vertex>) {
+    let mut points: Vec<Vector2f> = points.to_vec();
+    points.dedup();
+    if points.len() < 2 {
+        return;
+    }
+    let last = points.len() - 1;
+    let first_direction = normalize(points[1] - points[0]);
+    let last_direction = normalize(points[last] - points[last - 1]);
+    if cap == LineCap::Square {
+        points[0] -= first_direction * half_width;
+        points[last] += last_direction * half_width;
+    }
+    let mut previous_normal: Option<Vector2f> = None;
+    for pair in points.windows(2) {
+        let direction = normalize(pair[1] - pair[0]);
+        let normal = Vector2f::new(-direction.y, direction.x) * half_width;
+        let (a, b) = (pair[0] + normal, pair[0] - normal);
+        let (c, d) = (pair[1] + normal, pair[1] - normal);
+        push_triangle(out, a, b, c, color);
+        push_triangle(out, b, d, c, color);
+        // bevel the join so there's no notch on the outside of a bend
+        if let Some(prev) = previous_normal {
+            push_triangle(out, pair[0], pair[0] + prev, a, color);
+            push_triangle(out, pair[0], pair[0] - prev, b, color);
+        }
+        previous_normal = Some(normal);
+    }
+    if cap == LineCap::Round {
+        push_round_cap(out, points[0], -first_direction, half_width, color);
+        push_round_cap(out, points[last], last_direction, half_width, color);
+    }
+}
+
+/// tessellates every line in a style, `world_per_pixel` converts the pixel sizes in the style into world units
+pub fn tessellate_lines(lines: &[Vec<Vector2f>], style: &LineStyle, world_per_pixel: f32) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    let half_width = style.width * world_per_pixel / 2.0;
+    let dash: Vec<f32> = style.dash.iter().map(|x| x * world_per_pixel).collect();
+    for line in lines {
+        for piece in dash_polyline(line, &dash) {
+            tessellate_polyline(&piece, half_width, style.cap, style.color, &mut vertices);
+        }
+    }
+    vertices
+}
+
+/// linear features like COALNE and DEPCNT, drawn as thick lines instead of filled shapes
+pub struct LineGeometry {
+    pub lines: Vec<Vec<Vector2f>>,
+    pub style: LineStyle,
+    pub extent: LayerExtent,
+    // triangles from the last tessellation along with the world_per_pixel they were built for
+    cache: RefCell<Option<(f32, TiledVertices)>>,
+}
+
+impl LineGeometry {
+    pub fn new(lines: Vec<Vec<Vector2f>>, style: LineStyle) -> LineGeometry {
+        let mut extent = LayerExtent::default();
+        for point in lines.iter().flatten() {
+            extent.MinX = extent.MinX.min(point.x);
+            extent.MaxX = extent.MaxX.max(point.x);
+            extent.MinY = extent.MinY.min(point.y);
+            extent.MaxY = extent.MaxY.max(point.y);
+        }
+        LineGeometry { lines, style, extent, cache: RefCell::new(None) }
+    }
+}
+
+impl Plotable for LineGeometry {
+    fn render(&self, window: &mut dyn RenderTarget, view: &SfBox<View>) {
+        if self.lines.is_empty() || !is_extent_in_view(view, &self.extent) {
+            return;
+        }
+        let world_per_pixel = view.size().y / window.size().y as f32;
+        let mut cache = self.cache.borrow_mut();
+        let stale = match &*cache {
+            Some((built_for, _)) => ((world_per_pixel / built_for) - 1.0).abs() > RETESSELLATE_RATIO,
+            None => true,
+        };
+        if stale {
+            let vertices = tessellate_lines(&self.lines, &self.style, world_per_pixel);
+            *cache = Some((world_per_pixel, TiledVertices::new(&vertices)));
+        }
+        if let Some((_, tiles)) = &*cache {
+            tiles.draw(window, view);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(points: &[(f32, f32)]) -> Vec<Vector2f> {
+        points.iter().map(|(x, y)| Vector2f::new(*x, *y)).collect()
+    }
+
+    #[test]
+    fn dashes() {
+        let corner = points(&[(0.0, 0.0), (3.0, 0.0), (3.0, 4.0)]);
+        assert_eq!(dash_polyline(&corner, &[2.0, 1.0]), vec![points(&[(0.0, 0.0), (2.0, 0.0)]), points(&[(3.0, 0.0), (3.0, 2.0)]), points(&[(3.0, 3.0), (3.0, 4.0)])]);
+        // a dash that runs past a vertex goes round the corner and the pattern carries on from where it left off
+        assert_eq!(dash_polyline(&corner, &[4.0, 1.0]), vec![points(&[(0.0, 0.0), (3.0, 0.0), (3.0, 1.0)]), points(&[(3.0, 2.0), (3.0, 4.0)])]);
+        // no pattern is a solid line
+        assert_eq!(dash_polyline(&corner, &[]), vec![corner.clone()]);
+        assert_eq!(dash_polyline(&corner, &[0.0, 0.0]), vec![corner.clone()]);
+    }
+
+    #[test]
+    fn empty_lines() {
+        assert_eq!(dash_polyline(&[], &[2.0, 1.0]), vec![Vec::new()]);
+        let point = points(&[(1.0, 1.0), (1.0, 1.0)]);
+        let mut vertices = Vec::new();
+        for piece in dash_polyline(&point, &[2.0, 1.0]) {
+            tessellate_polyline(&piece, 1.0, LineCap::Round, Color::BLACK, &mut vertices);
+        }
+        tessellate_polyline(&[], 1.0, LineCap::Round, Color::BLACK, &mut vertices);
+        assert!(vertices.is_empty());
+    }
+
+    #[test]
+    fn vertex_counts() {
+        let count = |line: &[Vector2f], cap: LineCap| {
+            let mut vertices = Vec::new();
+            tessellate_polyline(line, 1.0, cap, Color::BLACK, &mut vertices);
+            vertices.len()
+        };
+        let straight = points(&[(0.0, 0.0), (10.0, 0.0)]);
+        let bent = points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        // two triangles a segment and two more for the bevel at each bend
+        assert_eq!(count(&straight, LineCap::Butt), 6);
+        assert_eq!(count(&bent, LineCap::Butt), 18);
+        assert_eq!(count(&straight, LineCap::Square), 6);
+        // and half a circle at each end
+        assert_eq!(count(&straight, LineCap::Round), 6 + 2 * ROUND_CAP_SEGMENTS * 3);
+        assert_eq!(count(&bent, LineCap::Round), 18 + 2 * ROUND_CAP_SEGMENTS * 3);
+    }
+
+    #[test]
+    fn square_caps_reach_past_the_ends() {
+        let mut vertices = Vec::new();
+        tessellate_polyline(&points(&[(0.0, 0.0), (10.0, 0.0)]), 1.0, LineCap::Square, Color::BLACK, &mut vertices);
+        let xs: Vec<f32> = vertices.iter().map(|x| x.position.x).collect();
+        assert_eq!(xs.iter().copied().fold(f32::MAX, f32::min), -1.0);
+        assert_eq!(xs.iter().copied().fold(f32::MIN, f32::max), 11.0);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+
+use geo::{Coord, LineString, Polygon, Simplify};
+use log::debug;
+use sfml::graphics::{Color, Vertex};
+
+use crate::geometry::{rings, triangles_from_scaled_polygons};
+use crate::tiles::TiledVertices;
+
+// how far, in world meters, each level of detail may move a line from where the chart has it.
+// the first level is the full source geometry
+const LOD_TOLERANCES: [f64; 5] = [0.0, 4.0, 16.0, 64.0, 256.0];
+// a level is used once its error is less than this many screen pixels
+const LOD_PIXEL_ERROR: f64 = 0.5;
+// a level that keeps more of the vertices than this isn't worth the memory
+const LOD_MIN_REDUCTION: f64 = 0.75;
+
+type CoordKey = (u64, u64);
+
+fn coord_key(c: Coord) -> CoordKey {
+    (c.x.to_bits(), c.y.to_bits())
+}
+
+/// ring coordinates without the closing repeat of the first one
+fn open_ring(ring: &LineString) -> &[Coord] {
+    let coords = &ring.0[..];
+    if coords.len() > 1 && coords.first() == coords.last() { &coords[..coords.len() - 1] } else { coords }
+}
+
+/// Douglas–Peucker on a run of coordinates that keeps both ends. Runs are always simplified
+/// in the same direction so two polygons sharing a run end up with exactly the same points.
+fn simplify_chain(chain: &[Coord], epsilon: f64) -> Vec<Coord> {
+    let (first, last) = (coord_key(chain[0]), coord_key(chain[chain.len() - 1]));
+    let reversed = if first == last { coord_key(chain[1]) > coord_key(chain[chain.len() - 2]) } else { first > last };
+    let mut line = LineString::from(chain.to_vec());
+    if reversed {
+        line.0.reverse();
+    }
+    let mut simplified = line.simplify(&epsilon).0;
+    if reversed {
+        simplified.reverse();
+    }
+    simplified
+}
+
+/// Simplifies polygons that share edges, like the DEPARE of one cell, without opening gaps between them.
+/// Every ring is cut at its nodes, the vertices where more than two edges meet, and only the runs between
+/// nodes are simplified. Nodes never move and shared runs simplify the same way from either side, so
+/// neighbours keep a common boundary. Polygons that collapse at this tolerance come back as None.
+pub fn simplify_polygons(polygons: &[Polygon], epsilon: f64) -> Vec<Option<Polygon>> {
+    let mut neighbours: HashMap<CoordKey, HashSet<CoordKey>> = HashMap::new();
+    for ring in polygons.iter().flat_map(rings) {
+        for line in ring.lines() {
+            let (start, end) = (coord_key(line.start), coord_key(line.end));
+            if start != end {
+                neighbours.entry(start).or_default().insert(end);
+                neighbours.entry(end).or_default().insert(start);
+            }
+        }
+    }
+    let is_node = |c: &Coord| neighbours.get(&coord_key(*c)).is_none_or(|x| x.len() != 2);
+
+    let simplify_ring = |ring: &LineString| -> Option<LineString> {
+        let coords = open_ring(ring);
+        if coords.len() < 3 {
+            return None;
+        }
+        // rings with no nodes start at their smallest coordinate so both sides agree on where that is
+        let start = match coords.iter().position(is_node) {
+            Some(start) => start,
+            None => (0..coords.len()).min_by_key(|x| coord_key(coords[*x])).unwrap(),
+        };
+        let mut rotated: Vec<Coord> = coords[start..].iter().chain(&coords[..start]).copied().collect();
+        rotated.push(rotated[0]);
+        let mut simplified = vec![rotated[0]];
+        let mut chain_start = 0;
+        for i in 1..rotated.len() {
+            if i == rotated.len() - 1 || is_node(&rotated[i]) {
+                simplified.extend(simplify_chain(&rotated[chain_start..=i], epsilon).into_iter().skip(1));
+                chain_start = i;
+            }
+        }
+        if simplified.len() < 4 {
+            return None;
+        }
+        Some(LineString::from(simplified))
+    };
+
+    polygons
+        .iter()
+        .map(|polygon| {
+            let exterior = simplify_ring(polygon.exterior())?;
+            let interiors = polygon.interiors().iter().filter_map(simplify_ring).collect();
+            Some(Polygon::new(exterior, interiors))
+        })
+        .collect()
+}
+
+/// TRIANGLES list for polygons, each filled in its own colour
+pub fn triangle_vertices(polygons: &[Polygon], colors: &[Color]) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for (polygon, color) in polygons.iter().zip(colors) {
+        for triangle in triangles_from_scaled_polygons(&vec![polygon.clone()]) {
+            for point in [triangle.0, triangle.1, triangle.2] {
+                vertices.push(Vertex::with_pos_color((point.x as f32, point.y as f32).into(), *color));
+            }
+        }
+    }
+    vertices
+}
+
+/// A filled layer at a few levels of detail, from the full chart geometry down to coarse outlines
+/// for when the view is zoomed far out and the detail would be lost in a pixel anyway.
+#[derive(Debug)]
+pub struct LodTiles {
+    // the tolerance each level was simplified with and its triangles, finest first
+    levels: Vec<(f64, TiledVertices)>,
+}
+
+impl LodTiles {
+    pub fn empty() -> LodTiles {
+        LodTiles { levels: vec![(0.0, TiledVertices::new(&[]))] }
+    }
+
+    /// `full` is the already triangulated source geometry, coarser levels are simplified from `polygons`
+    pub fn build(polygons: &[Polygon], colors: &[Color], full: &[Vertex]) -> LodTiles {
+        let mut levels = vec![(0.0, TiledVertices::new_static(full))];
+        let mut last_count = polygons.iter().map(|x| rings(x).map(|r| r.0.len()).sum::<usize>()).sum::<usize>();
+        for epsilon in LOD_TOLERANCES.into_iter().skip(1) {
+            let simplified = simplify_polygons(polygons, epsilon);
+            let (kept, kept_colors): (Vec<Polygon>, Vec<Color>) = simplified.into_iter().zip(colors).filter_map(|(p, c)| Some((p?, *c))).unzip();
+            let count = kept.iter().map(|x| rings(x).map(|r| r.0.len()).sum::<usize>()).sum::<usize>();
+            if count as f64 > last_count as f64 * LOD_MIN_REDUCTION {
+                continue;
+            }
+            debug!("Level of detail at {epsilon}m keeps {count} of {last_count} points");
+            levels.push((epsilon, TiledVertices::new_static(&triangle_vertices(&kept, &kept_colors))));
+            last_count = count;
+        }
+        LodTiles { levels }
+    }
+
+    /// the coarsest level that still looks right at this many world meters per pixel
+    pub fn for_scale(&self, world_per_pixel: f32) -> &TiledVertices {
+        let allowed = world_per_pixel as f64 * LOD_PIXEL_ERROR;
+        let level = self.levels.iter().rev().find(|(epsilon, _)| *epsilon <= allowed).unwrap_or(&self.levels[0]);
+        &level.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::polygon;
+
+    use super::*;
+
+    // the edge the two polygons share, wiggling either side of x = 10
+    const SHARED: [(f64, f64); 6] = [(10.0, 0.0), (10.1, 2.0), (9.9, 4.0), (10.1, 6.0), (9.9, 8.0), (10.0, 10.0)];
+
+    fn neighbours() -> Vec<Polygon> {
+        let mut left = vec![(0.0, 0.0)];
+        left.extend(SHARED);
+        left.push((0.0, 10.0));
+        let mut right: Vec<(f64, f64)> = vec![(20.0, 10.0), (20.0, 0.0)];
+        right.extend(SHARED);
+        vec![Polygon::new(LineString::from(left), vec![]), Polygon::new(LineString::from(right), vec![])]
+    }
+
+    // the points of a polygon on its side of the shared edge, in order up the edge
+    fn shared_run(polygon: &Polygon) -> Vec<(f64, f64)> {
+        let mut run: Vec<(f64, f64)> = open_ring(polygon.exterior()).iter().filter(|x| (x.x - 10.0).abs() < 1.0).map(|x| (x.x, x.y)).collect();
+        run.sort_by(|a, b| a.1.total_cmp(&b.1));
+        run
+    }
+
+    #[test]
+    fn shared_edges_stay_shared() {
+        for epsilon in [0.05, 0.5] {
+            let simplified: Vec<Polygon> = simplify_polygons(&neighbours(), epsilon).into_iter().map(|x| x.unwrap()).collect();
+            assert_eq!(shared_run(&simplified[0]), shared_run(&simplified[1]), "{epsilon}");
+        }
+        // too small to take out
+        let kept = simplify_polygons(&neighbours(), 0.05)[0].clone().unwrap();
+        assert_eq!(shared_run(&kept), SHARED.to_vec());
+        // straightened out, but the nodes at either end stay put
+        let straightened = simplify_polygons(&neighbours(), 0.5)[0].clone().unwrap();
+        assert_eq!(shared_run(&straightened), vec![(10.0, 0.0), (10.0, 10.0)]);
+        for polygon in simplify_polygons(&neighbours(), 0.5).into_iter().flatten() {
+            assert!(polygon.exterior().0.iter().any(|x| (x.x, x.y) == (10.0, 0.0)));
+            assert!(polygon.exterior().0.iter().any(|x| (x.x, x.y) == (10.0, 10.0)));
+        }
+    }
+
+    #[test]
+    fn collapsed() {
+        let small = polygon![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 0.0, y: 1.0)];
+        assert_eq!(simplify_polygons(std::slice::from_ref(&small), 5.0), vec![None]);
+        assert_eq!(simplify_polygons(std::slice::from_ref(&small), 0.1), vec![Some(small)]);
+    }
+}
@@ -0,0 +1,516 @@
+use std::fmt;
+
+/// UTC time of day from a sentence
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NmeaTime {
+    pub hour: u32,
+    pub minute: u32,
+    pub second: f64,
+}
+
+impl NmeaTime {
+    pub fn seconds_of_day(&self) -> f64 {
+        (self.hour * 3600 + self.minute * 60) as f64 + self.second
+    }
+
+    /// wraps around at midnight like a receiver clock does
+    pub fn from_seconds_of_day(seconds: f64) -> NmeaTime {
+        let seconds = seconds.rem_euclid(86400.0);
+        let whole = seconds as u32;
+        NmeaTime { hour: whole / 3600, minute: whole / 60 % 60, second: seconds - (whole - whole % 60) as f64 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NmeaDate {
+    pub day: u32,
+    pub month: u32,
+    pub year: u32,
+}
+
+/// Recommended minimum data
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rmc {
+    pub time: Option<NmeaTime>,
+    pub valid: bool,
+    // lon, lat in degrees
+    pub position: Option<(f64, f64)>,
+    pub sog: Option<f64>,
+    pub cog: Option<f64>,
+    pub date: Option<NmeaDate>,
+    // east is positive
+    pub magnetic_variation: Option<f64>,
+}
+
+/// Fix data
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gga {
+    pub time: Option<NmeaTime>,
+    pub position: Option<(f64, f64)>,
+    // 0 is no fix, 8 is a simulator
+    pub quality: u32,
+    pub satellites: Option<u32>,
+    pub hdop: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+/// Geographic position
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gll {
+    pub position: Option<(f64, f64)>,
+    pub time: Option<NmeaTime>,
+    pub valid: bool,
+}
+
+/// Track and speed over ground
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vtg {
+    pub cog: Option<f64>,
+    pub cog_magnetic: Option<f64>,
+    pub sog: Option<f64>,
+}
+
+/// Magnetic heading with deviation and variation, east is positive
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hdg {
+    pub heading: Option<f64>,
+    pub deviation: Option<f64>,
+    pub variation: Option<f64>,
+}
+
+impl Hdg {
+    /// heading corrected to true north, if we know the variation
+    pub fn true_heading(&self) -> Option<f64> {
+        let heading = self.heading? + self.deviation.unwrap_or(0.0) + self.variation?;
+        Some(heading.rem_euclid(360.0))
+    }
+}
+
+/// DOP and active satellites
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gsa {
+    pub auto_mode: bool,
+    // 1 no fix, 2 2D, 3 3D
+    pub fix_type: u32,
+    pub satellites: Vec<u32>,
+    pub pdop: Option<f64>,
+    pub hdop: Option<f64>,
+    pub vdop: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SatelliteInView {
+    pub prn: u32,
+    pub elevation: Option<f64>,
+    pub azimuth: Option<f64>,
+    pub snr: Option<f64>,
+}
+
+/// Satellites in view, spread over several sentences
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gsv {
+    pub message_count: u32,
+    pub message_number: u32,
+    pub satellites_in_view: u32,
+    pub satellites: Vec<SatelliteInView>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sentence {
+    Rmc(Rmc),
+    Gga(Gga),
+    Gll(Gll),
+    Vtg(Vtg),
+    // true heading in degrees
+    Hdt(Option<f64>),
+    Hdg(Hdg),
+    Gsa(Gsa),
+    Gsv(Gsv),
+}
+
+impl Sentence {
+    /// the UTC time stamp, for the sentences that carry one
+    pub fn time(&self) -> Option<NmeaTime> {
+        match self {
+            Sentence::Rmc(rmc) => rmc.time,
+            Sentence::Gga(gga) => gga.time,
+            Sentence::Gll(gll) => gll.time,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NmeaError {
+    NotASentence,
+    BadChecksum { expected: u8, found: u8 },
+    Unsupported(String),
+    BadField(&'static str, String),
+}
+
+impl fmt::Display for NmeaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NmeaError::NotASentence => write!(f, "not an NMEA sentence"),
+            NmeaError::BadChecksum { expected, found } => write!(f, "bad checksum, expected {expected:02X} found {found:02X}"),
+            NmeaError::Unsupported(kind) => write!(f, "unsupported sentence {kind}"),
+            NmeaError::BadField(name, value) => write!(f, "bad {name} field '{value}'"),
+        }
+    }
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+fn parse_number<T: std::str::FromStr>(name: &'static str, field: Option<&&str>) -> Result<Option<T>, NmeaError> {
+    match field {
+        None => Ok(None),
+        Some(&"") => Ok(None),
+        Some(x) => x.parse::<T>().map(Some).map_err(|_| NmeaError::BadField(name, x.to_string())),
+    }
+}
+
+fn parse_time(field: Option<&&str>) -> Result<Option<NmeaTime>, NmeaError> {
+    let field = match field {
+        Some(x) if x.len() >= 6 && x.is_ascii() => *x,
+        Some(&"") => return Ok(None),
+        None => return Ok(None),
+        Some(x) => return Err(NmeaError::BadField("time", x.to_string())),
+    };
+    let bad = || NmeaError::BadField("time", field.to_string());
+    let hour = field[0..2].parse::<u32>().map_err(|_| bad())?;
+    let minute = field[2..4].parse::<u32>().map_err(|_| bad())?;
+    let second = field[4..].parse::<f64>().map_err(|_| bad())?;
+    Ok(Some(NmeaTime { hour, minute, second }))
+}
+
+fn parse_date(field: Option<&&str>) -> Result<Option<NmeaDate>, NmeaError> {
+    let field = match field {
+        Some(x) if x.len() == 6 && x.is_ascii() => *x,
+        Some(&"") => return Ok(None),
+        None => return Ok(None),
+        Some(x) => return Err(NmeaError::BadField("date", x.to_string())),
+    };
+    let bad = || NmeaError::BadField("date", field.to_string());
+    let day = field[0..2].parse::<u32>().map_err(|_| bad())?;
+    let month = field[2..4].parse::<u32>().map_err(|_| bad())?;
+    let year = field[4..6].parse::<u32>().map_err(|_| bad())?;
+    // two digit years, GPS didn't exist before the 80s
+    let year = if year >= 80 { 1900 + year } else { 2000 + year };
+    Ok(Some(NmeaDate { day, month, year }))
+}
+
+/// ddmm.mmmm or dddmm.mmmm plus a hemisphere into signed degrees
+fn parse_coordinate(name: &'static str, value: Option<&&str>, hemisphere: Option<&&str>, degree_digits: usize) -> Result<Option<f64>, NmeaError> {
+    let value = match value {
+        Some(x) if !x.is_empty() => *x,
+        _ => return Ok(None),
+    };
+    if value.len() < degree_digits + 2 || !value.is_ascii() {
+        return Err(NmeaError::BadField(name, value.to_string()));
+    }
+    let bad = || NmeaError::BadField(name, value.to_string());
+    let degrees = value[..degree_digits].parse::<f64>().map_err(|_| bad())?;
+    let minutes = value[degree_digits..].parse::<f64>().map_err(|_| bad())?;
+    let coordinate = degrees + minutes / 60.0;
+    match hemisphere.copied() {
+        Some("N") | Some("E") => Ok(Some(coordinate)),
+        Some("S") | Some("W") => Ok(Some(-coordinate)),
+        other => Err(NmeaError::BadField(name, other.unwrap_or("").to_string())),
+    }
+}
+
+/// lat/lon fields starting at `index` into a lon, lat pair
+fn parse_position(fields: &[&str], index: usize) -> Result<Option<(f64, f64)>, NmeaError> {
+    let lat = parse_coordinate("latitude", fields.get(index), fields.get(index + 1), 2)?;
+    let lon = parse_coordinate("longitude", fields.get(index + 2), fields.get(index + 3), 3)?;
+    match (lon, lat) {
+        (Some(lon), Some(lat)) => Ok(Some((lon, lat))),
+        _ => Ok(None),
+    }
+}
+
+/// applies the E/W sign that follows some fields, east is positive
+fn signed_east(value: Option<f64>, direction: Option<&&str>) -> Option<f64> {
+    match direction.copied() {
+        Some("W") => value.map(|x| -x),
+        _ => value,
+    }
+}
+
+/// parses one line like `$GPRMC,...*hh`, the talker id is ignored so GP, GN, HE, II etc all work
+pub fn parse_sentence(line: &str) -> Result<Sentence, NmeaError> {
+    let line = line.trim();
+    if !line.is_ascii() {
+        return Err(NmeaError::NotASentence);
+    }
+    let body = line.strip_prefix('$').ok_or(NmeaError::NotASentence)?;
+    let body = match body.split_once('*') {
+        Some((data, sum)) => {
+            let found = u8::from_str_radix(sum.trim(), 16).map_err(|_| NmeaError::BadField("checksum", sum.to_string()))?;
+            let expected = checksum(data);
+            if expected != found {
+                return Err(NmeaError::BadChecksum { expected, found });
+            }
+            data
+        }
+        None => body,
+    };
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    if address.len() < 5 {
+        return Err(NmeaError::NotASentence);
+    }
+    let kind = &address[address.len() - 3..];
+    let f = &fields[1..];
+    let sentence = match kind {
+        "RMC" => Sentence::Rmc(Rmc {
+            time: parse_time(f.first())?,
+            valid: f.get(1) == Some(&"A"),
+            position: parse_position(f, 2)?,
+            sog: parse_number("speed", f.get(6))?,
+            cog: parse_number("course", f.get(7))?,
+            date: parse_date(f.get(8))?,
+            magnetic_variation: signed_east(parse_number("variation", f.get(9))?, f.get(10)),
+        }),
+        "GGA" => Sentence::Gga(Gga {
+            time: parse_time(f.first())?,
+            position: parse_position(f, 1)?,
+            quality: parse_number("quality", f.get(5))?.unwrap_or(0),
+            satellites: parse_number("satellites", f.get(6))?,
+            hdop: parse_number("hdop", f.get(7))?,
+            altitude: parse_number("altitude", f.get(8))?,
+        }),
+        "GLL" => Sentence::Gll(Gll {
+            position: parse_position(f, 0)?,
+            time: parse_time(f.get(4))?,
+            // old receivers leave the status off entirely
+            valid: f.get(5).is_none_or(|x| *x == "A"),
+        }),
+        "VTG" => Sentence::Vtg(Vtg {
+            cog: parse_number("course", f.first())?,
+            cog_magnetic: parse_number("magnetic course", f.get(2))?,
+            sog: parse_number("speed", f.get(4))?,
+        }),
+        "HDT" => Sentence::Hdt(parse_number("heading", f.first())?),
+        "HDG" => Sentence::Hdg(Hdg {
+            heading: parse_number("heading", f.first())?,
+            deviation: signed_east(parse_number("deviation", f.get(1))?, f.get(2)),
+            variation: signed_east(parse_number("variation", f.get(3))?, f.get(4)),
+        }),
+        "GSA" => {
+            let mut satellites = Vec::new();
+            for i in 2..14 {
+                if let Some(prn) = parse_number::<u32>("prn", f.get(i))? {
+                    satellites.push(prn);
+                }
+            }
+            Sentence::Gsa(Gsa {
+                auto_mode: f.first() == Some(&"A"),
+                fix_type: parse_number("fix type", f.get(1))?.unwrap_or(1),
+                satellites,
+                pdop: parse_number("pdop", f.get(14))?,
+                hdop: parse_number("hdop", f.get(15))?,
+                vdop: parse_number("vdop", f.get(16))?,
+            })
+        }
+        "GSV" => {
+            let mut satellites = Vec::new();
+            let mut i = 3;
+            // a trailing signal id field (NMEA 4.10) isn't a whole satellite so stop before it
+            while i + 3 < f.len() {
+                if let Some(prn) = parse_number::<u32>("prn", f.get(i))? {
+                    satellites.push(SatelliteInView {
+                        prn,
+                        elevation: parse_number("elevation", f.get(i + 1))?,
+                        azimuth: parse_number("azimuth", f.get(i + 2))?,
+                        snr: parse_number("snr", f.get(i + 3))?,
+                    });
+                }
+                i += 4;
+            }
+            Sentence::Gsv(Gsv {
+                message_count: parse_number("message count", f.first())?.unwrap_or(1),
+                message_number: parse_number("message number", f.get(1))?.unwrap_or(1),
+                satellites_in_view: parse_number("satellites in view", f.get(2))?.unwrap_or(0),
+                satellites,
+            })
+        }
+        other => return Err(NmeaError::Unsupported(other.to_string())),
+    };
+    Ok(sentence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    fn position(sentence: Sentence) -> (f64, f64) {
+        match sentence {
+            Sentence::Rmc(rmc) => rmc.position.unwrap(),
+            Sentence::Gga(gga) => gga.position.unwrap(),
+            Sentence::Gll(gll) => gll.position.unwrap(),
+            other => panic!("no position in {other:?}"),
+        }
+    }
+
+    #[test]
+    fn checksum_matches() {
+        assert!(parse_sentence("$GPHDT,274.1,T*35").is_ok());
+        // lower case hex and trailing line endings are fine
+        assert!(parse_sentence("$HCHDG,101.1,,,7.1,W*3c\r\n").is_ok());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        assert_eq!(parse_sentence("$GPHDT,274.1,T*36"), Err(NmeaError::BadChecksum { expected: 0x35, found: 0x36 }));
+        // a changed digit with the old checksum
+        assert!(matches!(parse_sentence("$GPHDT,275.1,T*35"), Err(NmeaError::BadChecksum { .. })));
+        assert!(matches!(parse_sentence("$GPHDT,274.1,T*"), Err(NmeaError::BadField("checksum", _))));
+        assert!(matches!(parse_sentence("$GPHDT,274.1,T*ZZ"), Err(NmeaError::BadField("checksum", _))));
+    }
+
+    #[test]
+    fn rmc() {
+        let rmc = match parse_sentence("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A") {
+            Ok(Sentence::Rmc(rmc)) => rmc,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(rmc.time, Some(NmeaTime { hour: 12, minute: 35, second: 19.0 }));
+        assert!(rmc.valid);
+        let (lon, lat) = rmc.position.unwrap();
+        assert!(close(lat, 48.0 + 7.038 / 60.0));
+        assert!(close(lon, 11.0 + 31.0 / 60.0));
+        assert_eq!(rmc.sog, Some(22.4));
+        assert_eq!(rmc.cog, Some(84.4));
+        assert_eq!(rmc.date, Some(NmeaDate { day: 23, month: 3, year: 1994 }));
+        assert_eq!(rmc.magnetic_variation, Some(-3.1));
+    }
+
+    #[test]
+    fn gga() {
+        let gga = match parse_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47") {
+            Ok(Sentence::Gga(gga)) => gga,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(gga.time.map(|x| x.seconds_of_day()), Some(45319.0));
+        assert!(gga.position.is_some());
+        assert_eq!(gga.quality, 1);
+        assert_eq!(gga.satellites, Some(8));
+        assert_eq!(gga.hdop, Some(0.9));
+        assert_eq!(gga.altitude, Some(545.4));
+    }
+
+    #[test]
+    fn gll() {
+        let gll = match parse_sentence("$GPGLL,4916.45,N,12311.12,W,225444,A,*1D") {
+            Ok(Sentence::Gll(gll)) => gll,
+            other => panic!("{other:?}"),
+        };
+        let (lon, lat) = gll.position.unwrap();
+        assert!(close(lat, 49.0 + 16.45 / 60.0));
+        assert!(close(lon, -(123.0 + 11.12 / 60.0)));
+        assert_eq!(gll.time, Some(NmeaTime { hour: 22, minute: 54, second: 44.0 }));
+        assert!(gll.valid);
+    }
+
+    #[test]
+    fn gll_status() {
+        // V is a void fix
+        assert!(matches!(parse_sentence("$GPGLL,3751.65,S,14507.36,W,081836,V*37"), Ok(Sentence::Gll(Gll { valid: false, .. }))));
+        // older receivers send neither the time nor the status, that's taken as a fix
+        assert!(matches!(parse_sentence("$GPGLL,3751.65,S,14507.36,W*65"), Ok(Sentence::Gll(Gll { valid: true, time: None, .. }))));
+    }
+
+    #[test]
+    fn vtg() {
+        assert_eq!(
+            parse_sentence("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48"),
+            Ok(Sentence::Vtg(Vtg { cog: Some(54.7), cog_magnetic: Some(34.4), sog: Some(5.5) }))
+        );
+    }
+
+    #[test]
+    fn hdg() {
+        let hdg = match parse_sentence("$HCHDG,101.1,,,7.1,W*3C") {
+            Ok(Sentence::Hdg(hdg)) => hdg,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(hdg, Hdg { heading: Some(101.1), deviation: None, variation: Some(-7.1) });
+        assert!(close(hdg.true_heading().unwrap(), 94.0));
+        assert_eq!(parse_sentence("$GPHDT,274.1,T*35"), Ok(Sentence::Hdt(Some(274.1))));
+    }
+
+    #[test]
+    fn gsa() {
+        assert_eq!(
+            parse_sentence("$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39"),
+            Ok(Sentence::Gsa(Gsa { auto_mode: true, fix_type: 3, satellites: vec![4, 5, 9, 12, 24], pdop: Some(2.5), hdop: Some(1.3), vdop: Some(2.1) }))
+        );
+    }
+
+    #[test]
+    fn gsv() {
+        let gsv = match parse_sentence("$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75") {
+            Ok(Sentence::Gsv(gsv)) => gsv,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!((gsv.message_count, gsv.message_number, gsv.satellites_in_view), (2, 1, 8));
+        assert_eq!(gsv.satellites.iter().map(|x| x.prn).collect::<Vec<_>>(), vec![1, 2, 12, 14]);
+        assert_eq!(gsv.satellites[1], SatelliteInView { prn: 2, elevation: Some(17.0), azimuth: Some(308.0), snr: Some(41.0) });
+    }
+
+    #[test]
+    fn southern_and_western_hemispheres() {
+        let (lon, lat) = position(parse_sentence("$GPRMC,081836,A,3751.65,S,14507.36,E,000.0,360.0,130998,011.3,E*62").unwrap());
+        assert!(close(lat, -(37.0 + 51.65 / 60.0)));
+        assert!(close(lon, 145.0 + 7.36 / 60.0));
+        let (lon, lat) = position(parse_sentence("$GPGLL,3751.65,S,14507.36,W*65").unwrap());
+        assert!(lat < 0.0 && lon < 0.0);
+    }
+
+    #[test]
+    fn empty_fields() {
+        let rmc = match parse_sentence("$GPRMC,,V,,,,,,,,,*31") {
+            Ok(Sentence::Rmc(rmc)) => rmc,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(rmc, Rmc { time: None, valid: false, position: None, sog: None, cog: None, date: None, magnetic_variation: None });
+        assert!(matches!(parse_sentence("$GPGGA,,,,,,0,,,,,,,,*66"), Ok(Sentence::Gga(Gga { position: None, quality: 0, satellites: None, .. }))));
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        for line in [
+            "",
+            "$",
+            "$GP",
+            "GPHDT,274.1,T",
+            "$GPXYZ,1,2,3",
+            "$GPRMC,1235",
+            "$GPRMC,123519,A,48",
+            "$GPRMC,123519,A,4807.038,Q,01131.000,E",
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,fast",
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,2303",
+            "$GPGSV,2,1,08,xx,40,083,46",
+            "$GPRMC,12\u{e9}519,A",
+        ] {
+            assert!(parse_sentence(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn truncated_sentences_dont_panic() {
+        let full = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+        for end in 0..full.len() {
+            let _ = parse_sentence(&full[..end]);
+        }
+        // a cut off position is left out rather than half read
+        assert!(matches!(parse_sentence("$GPGGA,123519,4807.038,N"), Ok(Sentence::Gga(Gga { position: None, .. }))));
+    }
+}
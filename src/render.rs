@@ -1,105 +1,376 @@
-use sfml::graphics::{CircleShape, Color, Font, PrimitiveType, RenderStates, RenderTarget, RenderWindow, Shape, Transformable, Vertex, View};
+use sfml::graphics::{CircleShape, Color, FloatRect, Font, PrimitiveType, RectangleShape, RenderStates, RenderTarget, RenderWindow, Shape, Text, Transformable, Vertex, View};
+use sfml::system::Vector2f;
 use sfml::window::Style;
 use log::debug;
 use sfml::SfBox;
 
-use crate::config;
-use crate::geometry::{does_extent_collide, get_extent_area, BuoyLayer, DepthLayer, LayerExtent, Plotable, DEPARE};
+use crate::chart::ChartSet;
+use crate::config::Config;
+use crate::depth::{SNDG1, SNDG2};
+use crate::area::render_area_symbols;
+use crate::buoys::render_nav_aids;
+use crate::geometry::{does_extent_collide, LayerExtent, Plotable, DEPARE};
+use crate::gps::NavState;
+use crate::hazards::render_hazards;
+use crate::lights::render_lights;
+use crate::routes::render_routes;
+use crate::line::{tessellate_polyline, LineCap};
+use crate::text::TextBatch;
+use crate::tiles::TiledVertices;
+use crate::transform::Projection;
+use crate::units::{DepthUnit, Units, METERS_PER_NAUTICAL_MILE};
 
 use std::collections::HashMap;
 
-pub fn create_window() -> RenderWindow {
+pub fn create_window(config: &Config) -> RenderWindow {
     debug!("Creating window");
-    let resolution = config::get_resolution(); 
-    let window = RenderWindow::new(resolution, "SFML window", Style::NONE, &Default::default());
+    let resolution = config.resolution();
+    let window = RenderWindow::new(resolution, "SFML window", Style::DEFAULT, &Default::default());
     window
 
 }
 
-pub fn render_objects<T: Plotable>(window: &mut RenderWindow, plotvec: &Vec<T>, window_view: &SfBox<View>) {
+pub fn render_objects<T: Plotable>(window: &mut dyn RenderTarget, plotvec: &Vec<T>, window_view: &SfBox<View>) {
 
         // render code
+        // zoomed out layers draw a simplified copy, the full detail would be lost inside a pixel
+        let world_per_pixel = get_world_per_pixel(window_view, (window.size().x, window.size().y));
         for plot in plotvec {
-            plot.render(window, window_view)
+            plot.render_at_scale(window, window_view, world_per_pixel)
         }
 }
 
-//pub fn draw_vertex_buffer(window: &mut RenderWindow, vertex_buffer: &VertexBuffer) {
-//    info!("Drawing {} Vertices!", vertex_buffer.vertex_count());
-//    window.draw(vertex_buffer);
-//}
+/// the part of the world a view shows
+pub fn get_view_extent(view: &View) -> LayerExtent {
+    let view_center = view.center();
+    let view_size = view.size();
+    LayerExtent{MinX: view_center.x - view_size.x / 2.0, MaxX: view_center.x + view_size.x / 2.0, MinY: view_center.y - view_size.y / 2.0, MaxY: view_center.y + view_size.y / 2.0}
+}
 
 pub fn is_extent_in_view(view: &View, extent: &LayerExtent) -> bool {
     // if the extent is not in the view then return false
-    let view_center = view.center();
-    let view_size = view.size();
-    let view_extent = LayerExtent{MinX: view_center.x - view_size.x / 2.0, MaxX: view_center.x + view_size.x / 2.0, MinY: view_center.y - view_size.y / 2.0, MaxY: view_center.y + view_size.y / 2.0};
+    let view_extent = get_view_extent(view);
     // info!("Checking if extent {:?} is in view {:?}", extent, view_extent);
     does_extent_collide(&view_extent, extent)
 }
 
-pub fn draw_vertex_vector(window: &mut RenderWindow, vertices: &Vec<Vertex>, vertex_extent: &LayerExtent, view: &SfBox<View>) {
-    if !is_extent_in_view(view, vertex_extent) {
+/// only the tiles that touch the view get submitted, from vertex buffers where we have them
+pub fn draw_vertex_vector(window: &mut dyn RenderTarget, tiles: &TiledVertices, view: &SfBox<View>) {
+    if tiles.is_empty() || !is_extent_in_view(view, &tiles.extent) {
         // info!("Extent not in view!");
         return;
     }
-    window.draw_primitives(vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+    tiles.draw(window, view);
+}
 
+/// the kinds of symbols drawn over the chart, for looking up how far out they're shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symbology {
+    Routes,
+    AreaSymbols,
+    Hazards,
+    NavAids,
+    Lights,
 }
 
-pub fn render_soundg(window: &mut RenderWindow, depth_soundings: &DepthLayer,  font: &SfBox<Font>, scale: f32, view: &SfBox<View>) {
-    let view_extent = LayerExtent{MinX: view.center().x - view.size().x / 2.0, MaxX: view.center().x + view.size().x / 2.0, MinY: view.center().y - view.size().y / 2.0, MaxY: view.center().y + view.size().y / 2.0};
-    if !does_extent_collide(&view_extent, &depth_soundings.extent) {
-        // info!("Extent not in view!");
-        return;
+impl Symbology {
+    /// (symbols, labels): the symbols are left off when zoomed further out than this many world meters per pixel,
+    /// and their labels or depths sooner. Area symbols and nav aids have no labels
+    fn zoom_limits(self) -> (f32, f32) {
+        match self {
+            Symbology::Routes => (200.0, 30.0),
+            Symbology::AreaSymbols => (100.0, 0.0),
+            Symbology::Hazards => (200.0, 30.0),
+            Symbology::NavAids => (200.0, 0.0),
+            Symbology::Lights => (200.0, 30.0),
+        }
     }
-    let view_area = get_extent_area( &view_extent);
-    let soundg_area = get_extent_area(&depth_soundings.extent);
 
-    let soundg_scale = view_area / soundg_area;
-    // log::info!("Soundg scale: {}", soundg_scale);
-    if soundg_scale > 10.0 {
-        return;
+    pub fn shown(self, zoom: f32) -> bool {
+        zoom <= self.zoom_limits().0
+    }
+
+    pub fn labelled(self, zoom: f32) -> bool {
+        zoom <= self.zoom_limits().1
+    }
+}
+
+/// the extent grown by `margin` world units on every side
+pub fn grow_extent(extent: &LayerExtent, margin: f32) -> LayerExtent {
+    LayerExtent{MinX: extent.MinX - margin, MaxX: extent.MaxX + margin, MinY: extent.MinY - margin, MaxY: extent.MaxY + margin}
+}
+
+/// whether a symbol drawn around `position` and reaching `margin` world units from it shows in the view extent
+pub fn visible(position: Vector2f, margin: f32, view: &LayerExtent) -> bool {
+    position.x >= view.MinX - margin && position.x <= view.MaxX + margin && position.y >= view.MinY - margin && position.y <= view.MaxY + margin
+}
+
+/// fills a convex polygon as a triangle fan
+pub fn push_convex(out: &mut Vec<Vertex>, points: &[Vector2f], color: Color) {
+    for i in 1..points.len().saturating_sub(1) {
+        out.push(Vertex::with_pos_color(points[0], color));
+        out.push(Vertex::with_pos_color(points[i], color));
+        out.push(Vertex::with_pos_color(points[i + 1], color));
+    }
+}
+
+/// points along a circle around `center` going clockwise from one bearing to another
+pub fn bearing_arc(center: Vector2f, from: f64, to: f64, radius: f32) -> Vec<Vector2f> {
+    let to = if to <= from { to + 360.0 } else { to };
+    let steps = ((to - from) / 2.0).ceil().max(1.0) as usize;
+    (0..=steps).map(|i| center + bearing_direction(from + (to - from) * i as f64 / steps as f64) * radius).collect()
+}
+
+// soundings are rasterised at this size, they come out this many pixels high on screen
+pub const SOUNDING_FONT_SIZE: u32 = 16;
+// the smaller figure next to a sounding is this size compared to the whole part
+pub const SOUNDING_SUBSCRIPT_SCALE: f32 = 0.75;
+// soundings are thinned out to at most one in each square this many pixels across
+const SOUNDING_CELL_PIXELS: f32 = 48.0;
+// space in pixels kept clear around each sounding's figures
+const SOUNDING_LABEL_GAP: f32 = 2.0;
+
+/// The figures of one sounding laid out the way S-52 does: centred on the spot, decimetres (or feet on fathom
+/// charts) as a smaller figure beside and a little below the whole part, black and bold when shallower than
+/// `safety_depth` and grey otherwise. Used for soundings and for the depths of wrecks and obstructions.
+pub struct SoundingLabel {
+    whole: (String, Vector2f),
+    fraction: Option<(String, Vector2f)>,
+    scale: f32,
+    color: Color,
+    bold: bool,
+    // of both figures together, in world units
+    pub bounds: FloatRect,
+}
+
+impl SoundingLabel {
+    /// `scale` is world units per pixel of the whole part
+    pub fn new(batch: &TextBatch, depth: f64, unit: DepthUnit, center: Vector2f, scale: f32, safety_depth: f64) -> SoundingLabel {
+        let (whole, fraction) = unit.sounding_figures(depth);
+        let (color, bold) = if depth < safety_depth { (SNDG2, true) } else { (SNDG1, false) };
+        let small_scale = scale * SOUNDING_SUBSCRIPT_SCALE;
+        let whole_bounds = batch.measure(&whole, scale, bold);
+        let small_bounds = fraction.as_ref().map(|x| batch.measure(x, small_scale, bold));
+
+        // the figures together are centred on the sounding
+        let small_width = small_bounds.map_or(0.0, |x| x.width);
+        let left = center.x - (whole_bounds.width + small_width) / 2.0;
+        let top = center.y - whole_bounds.height / 2.0;
+        let mut bounds = FloatRect::new(left, top, whole_bounds.width, whole_bounds.height);
+        let whole = (whole, Vector2f::new(left - whole_bounds.left, top - whole_bounds.top));
+        let fraction = match (fraction, small_bounds) {
+            (Some(fraction), Some(small_bounds)) => {
+                let small_top = top + whole_bounds.height * 0.5;
+                bounds.width += small_bounds.width;
+                bounds.height = bounds.height.max(whole_bounds.height * 0.5 + small_bounds.height);
+                Some((fraction, Vector2f::new(left + whole_bounds.width - small_bounds.left, small_top - small_bounds.top)))
+            }
+            _ => None,
+        };
+        SoundingLabel { whole, fraction, scale, color, bold, bounds }
+    }
+
+    pub fn add_to(&self, batch: &mut TextBatch) {
+        batch.add(&self.whole.0, self.whole.1, self.scale, self.color, self.bold);
+        if let Some((fraction, position)) = &self.fraction {
+            batch.add(fraction, *position, self.scale * SOUNDING_SUBSCRIPT_SCALE, self.color, self.bold);
+        }
+    }
+}
+
+/// Draws soundings as SoundingLabels. Only the shallowest sounding
+/// in each screen cell is shown and any that would run into one already drawn is left out, so the view
+/// stays readable at every zoom. `scale` is world units per pixel.
+pub fn render_soundg(window: &mut dyn RenderTarget, mut soundings: Vec<(f64, f64, f64)>, font: &Font, scale: f32, view: &View, safety_depth: f64, unit: DepthUnit) {
+    let view_extent = get_view_extent(view);
+    soundings.retain(|(x, y, _)| (view_extent.MinX..=view_extent.MaxX).contains(&(*x as f32)) && (view_extent.MinY..=view_extent.MaxY).contains(&(*y as f32)));
+    // the shallow ones are what matters for not running aground, so they get first pick of the space
+    soundings.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    // cells are fixed to the world rather than the view so soundings don't jump around while panning
+    let cell_size = SOUNDING_CELL_PIXELS * scale;
+    let gap = SOUNDING_LABEL_GAP * scale;
+    let mut placed: HashMap<(i32, i32), FloatRect> = HashMap::new();
+    let mut batch = TextBatch::new(font, SOUNDING_FONT_SIZE);
+    for (x, y, depth) in soundings {
+        let cell = ((x as f32 / cell_size).floor() as i32, (y as f32 / cell_size).floor() as i32);
+        if placed.contains_key(&cell) {
+            continue;
+        }
+        let label = SoundingLabel::new(&batch, depth, unit, Vector2f::new(x as f32, y as f32), scale, safety_depth);
+        let bounds = label.bounds;
+        let padded = FloatRect::new(bounds.left - gap, bounds.top - gap, bounds.width + 2.0 * gap, bounds.height + 2.0 * gap);
+        // labels are smaller than a cell, so only the cells around this one can hold one it overlaps
+        let overlaps = (-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (cell.0 + dx, cell.1 + dy))).any(|x| placed.get(&x).is_some_and(|other| other.intersection(&padded).is_some()));
+        if overlaps {
+            continue;
+        }
+        placed.insert(cell, padded);
+        label.add_to(&mut batch);
     }
+    batch.draw(window);
+}
+
+/// draws one frame of everything in the chart set, shared by the window and headless output
+pub fn render_chart_set(window: &mut dyn RenderTarget, charts: &ChartSet, view: &SfBox<View>, zoom: f32, render_depth: bool) {
+    window.set_view(view);
+    window.clear(Color::BLACK);
 
+    let world_per_pixel = get_world_per_pixel(view, (window.size().x, window.size().y));
+    for plot in charts.plot_refs.iter().filter(|x| !x.style.is_overlay()) {
+        plot.render_at_scale(window, view, world_per_pixel);
+    }
+
+    for key in charts.depare_map.keys() {
+        let layers = charts.depare_map.get(key).unwrap();
+        render_objects(window, layers, view);
+    }
+    // see-through and hatched areas go over the depth shading
+    for plot in charts.plot_refs.iter().filter(|x| x.style.is_overlay()) {
+        plot.render_at_scale(window, view, world_per_pixel);
+    }
+    charts.safety_contour.render(window, view);
+    for plot in charts.plot_refs.iter() {
+        plot.lines.render(window, view);
+        render_area_symbols(window, &plot.symbols, plot.lines.style.color, zoom, view);
+    }
+    render_routes(window, &charts.routes, &charts.traffic_arrows, &charts.font, zoom, view);
 
-    for sounding in &depth_soundings.coordinates {
-        let mut text = sfml::graphics::Text::new(&format!("{:.0}", sounding.2 * 3.281), &font, 8);
-        
-        let pos = (sounding.0 as f32, sounding.1 as f32);
-        
-        text.set_position(pos);
-        text.set_fill_color(Color::WHITE);
-        text.scale((2.0 * scale, 2.0 * scale));
-        window.draw(&text);
+    if render_depth {
+        // soundings from every chart are thinned out together so overlapping charts don't double up
+        let soundings = charts.depth_index.soundings_in(&get_view_extent(view));
+        render_soundg(window, soundings, &charts.font, zoom, view, charts.safety.safety_depth, charts.units.depth);
     }
+    render_hazards(window, &charts.depth_index, &charts.font, zoom, view, charts.safety.safety_depth, charts.units.depth);
+    render_nav_aids(window, &charts.nav_aids, zoom, view);
+    render_lights(window, &charts.lights, &charts.font, zoom, view);
+}
+
+// own ship sizes are in pixels
+const OWN_SHIP_RADIUS: f32 = 8.0;
+const HEADING_LINE_LENGTH: f32 = 60.0;
+// the COG/SOG vector shows where we'll be this many minutes from now
+const COG_VECTOR_MINUTES: f64 = 6.0;
+
+/// unit vector on screen for a bearing in degrees true, north is up
+pub fn bearing_direction(bearing: f64) -> Vector2f {
+    let bearing = bearing.to_radians();
+    Vector2f::new(bearing.sin() as f32, -bearing.cos() as f32)
 }
 
-pub fn render_buoy(window: &mut RenderWindow, buoy: &BuoyLayer, scale: f32, view: &SfBox<View>) {
-    let view_extent = LayerExtent{MinX: view.center().x - view.size().x / 2.0, MaxX: view.center().x + view.size().x / 2.0, MinY: view.center().y - view.size().y / 2.0, MaxY: view.center().y + view.size().y / 2.0};
-    //info!("View extent: {:?}", view_extent);
-    //info!("Buoy extent: {:?}", buoy.extent);
-    //if !does_extent_collide(&view_extent, &buoy.extent) {
-        //info!("Extent not in view!");
-        //return;
-    //}
-    let view_area = get_extent_area( &view_extent);
-    let buoy_area = get_extent_area(&buoy.extent);
+/// own ship symbol with a heading line and a course over ground vector, grey once the fix goes stale
+pub fn render_own_ship(window: &mut dyn RenderTarget, nav: &NavState, projection: &dyn Projection, zoom: f32) {
+    let (lon, lat) = match nav.position {
+        Some(position) => position,
+        None => return,
+    };
+    let (x, y) = projection.forward((lon, lat));
+    let position = Vector2f::new(x as f32, y as f32);
+    let color = if nav.has_fix() { Color::rgb(255, 140, 0) } else { Color::rgb(128, 128, 128) };
+    let half_width = zoom;
 
-    let buoy_scale = view_area / buoy_area;
-    // log::info!("Buoy scale: {}", buoy_scale);
-    if buoy_scale > 40.0 {
+    let mut vertices = Vec::new();
+    if let Some(heading) = nav.heading {
+        let end = position + bearing_direction(heading) * (HEADING_LINE_LENGTH * zoom);
+        tessellate_polyline(&[position, end], half_width, LineCap::Butt, color, &mut vertices);
+    }
+    if let (Some(cog), Some(sog)) = (nav.cog, nav.sog) {
+        // mercator world meters grow with latitude, a real distance is stretched by sec(lat)
+        let meters = sog * METERS_PER_NAUTICAL_MILE * COG_VECTOR_MINUTES / 60.0;
+        let length = (meters / lat.to_radians().cos()) as f32;
+        let end = position + bearing_direction(cog) * length;
+        tessellate_polyline(&[position, end], half_width * 0.75, LineCap::Round, color, &mut vertices);
+    }
+    window.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+
+    let radius = OWN_SHIP_RADIUS * zoom;
+    let mut ship = CircleShape::new(radius, 30);
+    ship.set_origin((radius, radius));
+    ship.set_position(position);
+    ship.set_fill_color(Color::TRANSPARENT);
+    ship.set_outline_color(color);
+    ship.set_outline_thickness(2.0 * zoom);
+    window.draw(&ship);
+    let mut dot = CircleShape::new(2.0 * zoom, 12);
+    dot.set_origin((2.0 * zoom, 2.0 * zoom));
+    dot.set_position(position);
+    dot.set_fill_color(color);
+    window.draw(&dot);
+}
+
+const READOUT_FONT_SIZE: u32 = 16;
+const READOUT_MARGIN: f32 = 8.0;
+
+/// a box of text in the top left corner of the screen, drawn over the chart in pixels rather than world units
+pub fn render_readout(window: &mut dyn RenderTarget, lines: &[String], font: &Font) {
+    if lines.is_empty() {
         return;
     }
-    for vertex in &buoy.vertices {
-        
-        let mut buoy_circle = CircleShape::new(5.0 * scale, 30); 
-        buoy_circle.set_position(vertex.position);
-        buoy_circle.set_fill_color(vertex.color);
-        window.draw(&buoy_circle);
+    let chart_view = window.view().to_owned();
+    // the default view doesn't follow window resizes, so make one the size of the target
+    let size = window.size();
+    window.set_view(&View::from_rect(FloatRect::new(0.0, 0.0, size.x as f32, size.y as f32)));
+
+    let line_height = font.line_spacing(READOUT_FONT_SIZE);
+    let mut batch = TextBatch::new(font, READOUT_FONT_SIZE);
+    let mut width: f32 = 0.0;
+    for (i, line) in lines.iter().enumerate() {
+        let bounds = batch.measure(line, 1.0, false);
+        batch.add(line, Vector2f::new(2.0 * READOUT_MARGIN, READOUT_MARGIN + i as f32 * line_height), 1.0, Color::WHITE, false);
+        width = width.max(bounds.left + bounds.width);
     }
+    let mut background = RectangleShape::with_size((width + 2.0 * READOUT_MARGIN, lines.len() as f32 * line_height + READOUT_MARGIN).into());
+    background.set_position((READOUT_MARGIN, READOUT_MARGIN / 2.0));
+    background.set_fill_color(Color::rgba(0, 0, 0, 160));
+    window.draw(&background);
+    batch.draw(window);
+    window.set_view(&chart_view);
 }
-pub fn get_zoom(map: &HashMap<u16, Vec<DEPARE>>, view: &SfBox<View>) -> ((f32, f32), f32) {
+
+// the scale bar aims for about this many pixels long
+const SCALE_BAR_TARGET: f32 = 150.0;
+
+/// the biggest 1, 2 or 5 times a power of ten that fits under `value`
+fn nice_length(value: f64) -> f64 {
+    let power = 10f64.powf(value.log10().floor());
+    [5.0, 2.0, 1.0].iter().map(|x| x * power).find(|x| *x <= value).unwrap_or(power)
+}
+
+/// a black and white scale bar in the bottom left corner in whichever distance unit is set
+pub fn render_scale_bar(window: &mut dyn RenderTarget, projection: &dyn Projection, view: &View, zoom: f32, units: &Units, font: &Font) {
+    let center = view.center();
+    let (_, lat) = projection.inverse((center.x as f64, center.y as f64));
+    // mercator stretches things by sec(lat), so a world unit is less than a meter away from the equator
+    let meters_per_pixel = zoom as f64 * lat.to_radians().cos();
+    if meters_per_pixel.is_nan() || meters_per_pixel <= 0.0 {
+        return;
+    }
+    let meters_per_unit = units.distance.meters_per_unit();
+    let length = nice_length(SCALE_BAR_TARGET as f64 * meters_per_pixel / meters_per_unit);
+    let pixels = (length * meters_per_unit / meters_per_pixel) as f32;
+
+    let chart_view = window.view().to_owned();
+    let size = window.size();
+    window.set_view(&View::from_rect(FloatRect::new(0.0, 0.0, size.x as f32, size.y as f32)));
+    let left = 2.0 * READOUT_MARGIN;
+    let top = size.y as f32 - 3.0 * READOUT_MARGIN;
+    for half in 0..2 {
+        let mut bar = RectangleShape::with_size((pixels / 2.0, 6.0).into());
+        bar.set_position((left + half as f32 * pixels / 2.0, top));
+        bar.set_fill_color(if half == 0 { Color::BLACK } else { Color::WHITE });
+        bar.set_outline_color(Color::BLACK);
+        bar.set_outline_thickness(1.0);
+        window.draw(&bar);
+    }
+    let mut label = Text::new(&format!("{length} {}", units.distance.symbol()), font, READOUT_FONT_SIZE);
+    label.set_fill_color(Color::BLACK);
+    label.set_position((left, top - font.line_spacing(READOUT_FONT_SIZE)));
+    window.draw(&label);
+    window.set_view(&chart_view);
+}
+
+/// extent covering every DEPARE layer in the map
+pub fn get_depare_extent(map: &HashMap<u16, Vec<DEPARE>>) -> LayerExtent {
     let mut extents = Vec::new();
     for (_, depares) in map {
         for depare in depares {
@@ -112,6 +383,31 @@ pub fn get_zoom(map: &HashMap<u16, Vec<DEPARE>>, view: &SfBox<View>) -> ((f32, f
     let max_y = extents.iter().fold(f32::NEG_INFINITY, |acc, x| acc.max(x.MaxY));
     let new_extent = LayerExtent{MinX: min_x, MaxX: max_x, MinY: min_y, MaxY: max_y};
     debug!("new extent {:?} from extents: {:?}", new_extent, extents);
+    new_extent
+}
+
+/// grows the extent on one axis so it has the same aspect ratio as the image
+pub fn get_view_for_extent(extent: &LayerExtent, size: (u32, u32)) -> SfBox<View> {
+    let center = ((extent.MinX + extent.MaxX) / 2.0, (extent.MinY + extent.MaxY) / 2.0);
+    let mut width = (extent.MaxX - extent.MinX).abs();
+    let mut height = (extent.MaxY - extent.MinY).abs();
+    let aspect = size.0 as f32 / size.1 as f32;
+    if width / height > aspect {
+        height = width / aspect;
+    }
+    else {
+        width = height * aspect;
+    }
+    View::new(center.into(), (width, height).into())
+}
+
+/// how many world units one screen pixel covers, this is the `zoom` passed around for sizing text and symbols
+pub fn get_world_per_pixel(view: &View, target_size: (u32, u32)) -> f32 {
+    view.size().y / target_size.1 as f32
+}
+
+pub fn get_zoom(map: &HashMap<u16, Vec<DEPARE>>, view: &SfBox<View>) -> ((f32, f32), f32) {
+    let LayerExtent { MinX: min_x, MaxX: max_x, MinY: min_y, MaxY: max_y } = get_depare_extent(map);
 
     let x_el = (min_x + max_x) / 2.0;
     let y_el = (min_y + max_y) / 2.0;
@@ -122,7 +418,7 @@ pub fn get_zoom(map: &HashMap<u16, Vec<DEPARE>>, view: &SfBox<View>) -> ((f32, f
 
     let view_height = view.size().y;
     debug!("View height: {view_height}");
-    let y_zoom = (view_height / y_height).floor();
+    let y_zoom = view_height / y_height;
     debug!("y_height: {y_height}");
     debug!("y_zoom: {y_zoom}");
     (new_center, y_zoom)
@@ -0,0 +1,284 @@
+use gdal::Dataset;
+use gdal::vector::LayerAccess;
+use geo::InteriorPoint;
+use log::debug;
+use sfml::graphics::{Color, Font, PrimitiveType, RenderStates, RenderTarget, Vertex, View};
+use sfml::system::Vector2f;
+
+use crate::attributes::{Attributes, NavigationLineCategory, TrackCategory, TrafficFlow};
+use crate::convert::ConversionStats;
+use crate::geometry::{does_extent_collide, get_layers, lines_from_geo_geometry, project_geometry, LayerExtent};
+use crate::lights::CHMGD;
+use crate::line::{dash_polyline, length, tessellate_polyline, LineCap};
+use crate::render::{bearing_direction, get_view_extent, grow_extent, visible, Symbology};
+use crate::text::TextBatch;
+use crate::transform::Projection;
+
+pub const ROUTE_LAYERS: [&str; 3] = ["RECTRC", "NAVLNE", "DWRTCL"];
+// areas that can carry a direction of traffic in ORIENT
+pub const TRAFFIC_AREA_LAYERS: [&str; 2] = ["TSSLPT", "FAIRWY"];
+
+// S-52 grey for fairway arrows
+const CHGRD: Color = Color::rgb(125, 137, 140);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RouteClass {
+    // RECTRC
+    RecommendedTrack,
+    // NAVLNE, leading, transit and clearing lines
+    NavigationLine,
+    // DWRTCL
+    DeepWaterRoute,
+}
+
+impl RouteClass {
+    fn from_layer(name: &str) -> Option<RouteClass> {
+        match name {
+            "RECTRC" => Some(RouteClass::RecommendedTrack),
+            "NAVLNE" => Some(RouteClass::NavigationLine),
+            "DWRTCL" => Some(RouteClass::DeepWaterRoute),
+            _ => None,
+        }
+    }
+}
+
+/// One line of a recommended track, navigation line or deep water route centreline
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub class: RouteClass,
+    // world coordinates
+    pub line: Vec<Vector2f>,
+    pub extent: LayerExtent,
+    // ORIENT in degrees true
+    pub orient: Option<f64>,
+    pub traffic: Option<TrafficFlow>,
+    pub track: Option<TrackCategory>,
+    pub category: Option<NavigationLineCategory>,
+}
+
+impl Route {
+    /// tracks that follow fixed marks and leading lines are drawn solid, the rest dashed
+    pub fn is_leading(&self) -> bool {
+        self.track == Some(TrackCategory::FixedMarks) || self.category == Some(NavigationLineCategory::Leading)
+    }
+
+    pub fn color(&self) -> Color {
+        match self.class {
+            RouteClass::DeepWaterRoute => CHMGD,
+            _ => Color::BLACK,
+        }
+    }
+
+    /// bearings of the arrows on the line: one along ORIENT for one way traffic and a pair when TRAFIC says two way.
+    /// Without ORIENT a one way route goes the way it was digitised, and a line that doesn't give TRAFIC gets none
+    pub fn arrows(&self) -> Vec<f64> {
+        match (self.traffic, self.orient) {
+            (Some(TrafficFlow::Inbound | TrafficFlow::Outbound | TrafficFlow::OneWay), Some(orient)) => vec![orient],
+            (Some(TrafficFlow::Inbound | TrafficFlow::Outbound | TrafficFlow::OneWay), None) => line_bearing(&self.line).into_iter().collect(),
+            (Some(TrafficFlow::TwoWay), Some(orient)) => vec![orient, (orient + 180.0) % 360.0],
+            _ => Vec::new(),
+        }
+    }
+
+    /// leading lines and recommended tracks are labelled with their bearing like "270.0°"
+    pub fn label(&self) -> Option<String> {
+        match self.class {
+            RouteClass::RecommendedTrack | RouteClass::NavigationLine => self.orient.map(|x| format!("{x:05.1}°")),
+            RouteClass::DeepWaterRoute => None,
+        }
+    }
+
+    /// the point halfway along the line and the direction of the line there
+    pub fn midpoint(&self) -> Option<(Vector2f, Vector2f)> {
+        let lengths: Vec<f32> = self.line.windows(2).map(|x| length(x[1] - x[0])).collect();
+        let mut remaining = lengths.iter().sum::<f32>() / 2.0;
+        for (pair, segment) in self.line.windows(2).zip(lengths) {
+            if segment > 0.0 && remaining <= segment {
+                let direction = (pair[1] - pair[0]) / segment;
+                return Some((pair[0] + direction * remaining, direction));
+            }
+            remaining -= segment;
+        }
+        None
+    }
+}
+
+/// An arrow for the direction of traffic in a lane or fairway
+#[derive(Clone, Debug)]
+pub struct TrafficArrow {
+    pub position: Vector2f,
+    // degrees true
+    pub bearing: f64,
+    pub two_way: bool,
+    pub color: Color,
+}
+
+/// bearing from the first point of a line to the last, y is down so north is -y
+fn line_bearing(line: &[Vector2f]) -> Option<f64> {
+    let d = *line.last()? - *line.first()?;
+    if d.x == 0.0 && d.y == 0.0 {
+        return None;
+    }
+    Some((d.x as f64).atan2(-d.y as f64).to_degrees().rem_euclid(360.0))
+}
+
+/// the ones of `names` that are in `layers`, the layers config.toml turns on
+fn enabled<'a>(names: &[&'a str], layers: &[String]) -> Vec<&'a str> {
+    names.iter().copied().filter(|x| layers.iter().any(|layer| layer == x)).collect()
+}
+
+/// reads the recommended tracks, navigation lines and deep water route centrelines of a dataset that are in `layers`
+pub fn get_routes(ds: &Dataset, projection: &dyn Projection, layers: &[String]) -> Vec<Route> {
+    let mut routes = Vec::new();
+    for mut layer in get_layers(ds, enabled(&ROUTE_LAYERS, layers)) {
+        let layer_name = layer.name();
+        let class = match RouteClass::from_layer(&layer_name) {
+            Some(class) => class,
+            None => continue,
+        };
+        let mut stats = ConversionStats::default();
+        for feature in layer.features() {
+            let geometry = match stats.convert(feature.geometry()) {
+                Some(geometry) => project_geometry(geometry, projection),
+                None => continue,
+            };
+            let lines = lines_from_geo_geometry(geometry);
+            if lines.is_empty() {
+                stats.skipped += 1;
+            }
+            let attributes = Attributes::new(&feature);
+            for line in lines {
+                let line: Vec<Vector2f> = line.coords().map(|c| Vector2f::new(c.x as f32, c.y as f32)).collect();
+                let mut extent = LayerExtent::default();
+                for point in &line {
+                    extent.MinX = extent.MinX.min(point.x);
+                    extent.MaxX = extent.MaxX.max(point.x);
+                    extent.MinY = extent.MinY.min(point.y);
+                    extent.MaxY = extent.MaxY.max(point.y);
+                }
+                routes.push(Route {
+                    class,
+                    line,
+                    extent,
+                    orient: attributes.float("ORIENT"),
+                    traffic: attributes.enumeration(),
+                    track: attributes.enumeration(),
+                    category: attributes.enumeration(),
+                });
+            }
+        }
+        stats.report(&layer_name);
+    }
+    debug!("Found {} routes", routes.len());
+    routes
+}
+
+/// direction of traffic arrows for traffic separation lanes and fairways that give an ORIENT, when their layer is in `layers`
+pub fn get_traffic_arrows(ds: &Dataset, projection: &dyn Projection, layers: &[String]) -> Vec<TrafficArrow> {
+    let mut arrows = Vec::new();
+    for mut layer in get_layers(ds, enabled(&TRAFFIC_AREA_LAYERS, layers)) {
+        let layer_name = layer.name();
+        let lane = layer_name == "TSSLPT";
+        let mut stats = ConversionStats::default();
+        for feature in layer.features() {
+            let attributes = Attributes::new(&feature);
+            let bearing = match attributes.float("ORIENT") {
+                Some(bearing) => bearing,
+                None => continue,
+            };
+            let position = match stats.convert(feature.geometry()).and_then(|x| project_geometry(x, projection).interior_point()) {
+                Some(point) => Vector2f::new(point.x() as f32, point.y() as f32),
+                None => continue,
+            };
+            // lanes are always one way, fairways are two way only when TRAFIC says so
+            let two_way = !lane && attributes.enumeration() == Some(TrafficFlow::TwoWay);
+            arrows.push(TrafficArrow { position, bearing, two_way, color: if lane { CHMGD } else { CHGRD } });
+        }
+        stats.report(&layer_name);
+    }
+    debug!("Found {} traffic arrows", arrows.len());
+    arrows
+}
+
+// route sizes are in pixels
+const ROUTE_DASH: [f32; 2] = [8.0, 4.0];
+const ROUTE_CHEVRON: f32 = 8.0;
+const ROUTE_LABEL_SIZE: u32 = 12;
+// traffic arrows in lanes and fairways, from the middle to the tip
+const TRAFFIC_ARROW_LENGTH: f32 = 20.0;
+
+/// an open arrowhead with its tip at `tip`
+fn push_chevron(out: &mut Vec<Vertex>, tip: Vector2f, bearing: f64, zoom: f32, color: Color) {
+    let back = |side: f64| tip + bearing_direction(bearing + 180.0 + side) * (ROUTE_CHEVRON * zoom);
+    tessellate_polyline(&[back(-30.0), tip, back(30.0)], 0.75 * zoom, LineCap::Round, color, out);
+}
+
+/// Recommended tracks, navigation lines and deep water routes with arrows for the direction of traffic, plus
+/// the big outlined arrows of traffic separation lanes and fairways. Leading lines and tracks along fixed marks
+/// are solid and labelled with their bearing, the rest are dashed.
+pub fn render_routes(window: &mut dyn RenderTarget, routes: &[Route], arrows: &[TrafficArrow], font: &Font, zoom: f32, view: &View) {
+    if !Symbology::Routes.shown(zoom) {
+        return;
+    }
+    let view = get_view_extent(view);
+    let margin = 2.0 * TRAFFIC_ARROW_LENGTH * zoom;
+    let extent = grow_extent(&view, margin);
+    let dash = ROUTE_DASH.map(|x| x * zoom);
+    let mut vertices = Vec::new();
+    let mut labels = TextBatch::new(font, ROUTE_LABEL_SIZE);
+    for route in routes.iter().filter(|x| does_extent_collide(&extent, &x.extent)) {
+        let half_width = match route.class {
+            RouteClass::RecommendedTrack => 0.75 * zoom,
+            RouteClass::NavigationLine => 0.5 * zoom,
+            RouteClass::DeepWaterRoute => zoom,
+        };
+        let color = route.color();
+        if route.is_leading() {
+            tessellate_polyline(&route.line, half_width, LineCap::Butt, color, &mut vertices);
+        }
+        else {
+            for piece in dash_polyline(&route.line, &dash) {
+                tessellate_polyline(&piece, half_width, LineCap::Butt, color, &mut vertices);
+            }
+        }
+        let (middle, along) = match route.midpoint() {
+            Some(midpoint) => midpoint,
+            None => continue,
+        };
+        let bearings = route.arrows();
+        for bearing in &bearings {
+            // a pair for two way traffic sits either side of the middle pointing away from each other
+            let offset = if bearings.len() > 1 { ROUTE_CHEVRON * zoom } else { 0.0 };
+            push_chevron(&mut vertices, middle + bearing_direction(*bearing) * offset, *bearing, zoom, color);
+        }
+        if let Some(label) = route.label().filter(|_| Symbology::Routes.labelled(zoom)) {
+            // beside the line rather than on it
+            let side = Vector2f::new(-along.y, along.x) * (6.0 * zoom);
+            let bounds = labels.measure(&label, zoom, false);
+            let position = middle + side - Vector2f::new(bounds.width / 2.0 + bounds.left, bounds.height / 2.0 + bounds.top);
+            labels.add(&label, position, zoom, color, false);
+        }
+    }
+    for arrow in arrows {
+        let p = arrow.position;
+        if !visible(p, margin, &view) {
+            continue;
+        }
+        let forward = bearing_direction(arrow.bearing) * zoom;
+        let side = Vector2f::new(-forward.y, forward.x);
+        let at = |f: f32, s: f32| p + forward * f + side * s;
+        let tip = TRAFFIC_ARROW_LENGTH;
+        let head = TRAFFIC_ARROW_LENGTH * 0.4;
+        let mut outline = vec![at(tip, 0.0), at(head, 8.0), at(head, 3.0)];
+        if arrow.two_way {
+            outline.extend([at(-head, 3.0), at(-head, 8.0), at(-tip, 0.0), at(-head, -8.0), at(-head, -3.0)]);
+        }
+        else {
+            outline.extend([at(-tip, 3.0), at(-tip, -3.0)]);
+        }
+        outline.extend([at(head, -3.0), at(head, -8.0), at(tip, 0.0)]);
+        tessellate_polyline(&outline, zoom, LineCap::Round, arrow.color, &mut vertices);
+    }
+    window.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &RenderStates::default());
+    labels.draw(window);
+}
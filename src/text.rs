@@ -0,0 +1,101 @@
+use log::{info, warn};
+use sfml::graphics::{Color, FloatRect, Font, PrimitiveType, RenderStates, RenderTarget, Vertex};
+use sfml::system::Vector2f;
+use sfml::SfBox;
+
+// built into the binary so it runs from any directory, and without a font installed
+static EMBEDDED_FONT: &[u8] = include_bytes!("fonts/OpenSans-Regular.ttf");
+
+/// The font everything is written in: the one at `path` from the config if it loads, otherwise the built in one.
+/// It's loaded once and shared, SFML keeps the glyphs it has rendered on the font so they're only rasterised once.
+pub fn load_font(path: Option<&str>) -> SfBox<Font> {
+    if let Some(path) = path {
+        match Font::from_file(path) {
+            Some(font) => {
+                info!("Loaded font {path}");
+                return font;
+            }
+            None => warn!("Couldn't load font {path}, using the built in one"),
+        }
+    }
+    // the bytes are static so they outlive the font like SFML needs
+    unsafe { Font::from_memory(EMBEDDED_FONT) }.expect("the built in font is a valid ttf")
+}
+
+/// Lots of short strings drawn in one go. Each glyph is a textured quad out of the font's glyph texture,
+/// so a whole screen of labels is a single draw call instead of one per `Text`.
+pub struct TextBatch<'a> {
+    font: &'a Font,
+    // character size glyphs are rasterised at, `scale` then sizes them in the view
+    size: u32,
+    vertices: Vec<Vertex>,
+}
+
+impl<'a> TextBatch<'a> {
+    pub fn new(font: &'a Font, size: u32) -> TextBatch<'a> {
+        TextBatch { font, size, vertices: Vec::new() }
+    }
+
+    /// calls `glyph_at` with the pen position and glyph of each character, the baseline is at `size` like SFML's Text
+    fn layout(&self, string: &str, bold: bool, mut glyph_at: impl FnMut(f32, FloatRect, FloatRect)) {
+        let mut pen = 0.0;
+        let mut previous = None;
+        for c in string.chars() {
+            let codepoint = c as u32;
+            if let Some(previous) = previous {
+                pen += if bold { self.font.bold_kerning(previous, codepoint, self.size) } else { self.font.kerning(previous, codepoint, self.size) };
+            }
+            let glyph = self.font.glyph(codepoint, self.size, bold, 0.0);
+            let texture_rect = glyph.texture_rect();
+            let texture = FloatRect::new(texture_rect.left as f32, texture_rect.top as f32, texture_rect.width as f32, texture_rect.height as f32);
+            glyph_at(pen, glyph.bounds(), texture);
+            pen += glyph.advance();
+            previous = Some(codepoint);
+        }
+    }
+
+    /// where `string` would be drawn relative to its position, scaled by `scale`
+    pub fn measure(&self, string: &str, scale: f32, bold: bool) -> FloatRect {
+        let (mut min, mut max) = (Vector2f::new(f32::MAX, f32::MAX), Vector2f::new(f32::MIN, f32::MIN));
+        self.layout(string, bold, |pen, bounds, _| {
+            min.x = min.x.min(pen + bounds.left);
+            min.y = min.y.min(self.size as f32 + bounds.top);
+            max.x = max.x.max(pen + bounds.left + bounds.width);
+            max.y = max.y.max(self.size as f32 + bounds.top + bounds.height);
+        });
+        if min.x > max.x {
+            return FloatRect::new(0.0, 0.0, 0.0, 0.0);
+        }
+        FloatRect::new(min.x * scale, min.y * scale, (max.x - min.x) * scale, (max.y - min.y) * scale)
+    }
+
+    /// queues up `string` with its top left at `position`, scaled by `scale`
+    pub fn add(&mut self, string: &str, position: Vector2f, scale: f32, color: Color, bold: bool) {
+        let mut quads = Vec::new();
+        self.layout(string, bold, |pen, bounds, texture| {
+            let left = position.x + (pen + bounds.left) * scale;
+            let top = position.y + (self.size as f32 + bounds.top) * scale;
+            let (right, bottom) = (left + bounds.width * scale, top + bounds.height * scale);
+            let (tex_right, tex_bottom) = (texture.left + texture.width, texture.top + texture.height);
+            let corner = |x, y, u, v| Vertex::new(Vector2f::new(x, y), color, Vector2f::new(u, v));
+            let (top_left, top_right) = (corner(left, top, texture.left, texture.top), corner(right, top, tex_right, texture.top));
+            let (bottom_left, bottom_right) = (corner(left, bottom, texture.left, tex_bottom), corner(right, bottom, tex_right, tex_bottom));
+            quads.extend([top_left, top_right, bottom_left, top_right, bottom_right, bottom_left]);
+        });
+        self.vertices.extend(quads);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn draw(&self, window: &mut dyn RenderTarget) {
+        if self.is_empty() {
+            return;
+        }
+        // the glyph texture can grow while glyphs are added, so it's only looked up now they all have been
+        let mut states = RenderStates::default();
+        states.set_texture(Some(self.font.texture(self.size)));
+        window.draw_primitives(&self.vertices, PrimitiveType::TRIANGLES, &states);
+    }
+}
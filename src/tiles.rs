@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+use log::debug;
+use sfml::graphics::{PrimitiveType, RenderStates, RenderTarget, Vertex, VertexBuffer, VertexBufferUsage, View};
+
+use crate::geometry::{get_vertices_extent, LayerExtent};
+use crate::render::get_view_extent;
+
+// a layer is cut into at most this many tiles along its longer side
+const TILES_ACROSS: f32 = 32.0;
+
+/// One tile's triangles, kept in graphics memory when the driver gives us vertex buffers. Not Clone,
+/// copying one would upload it again
+#[derive(Debug)]
+pub enum Tile {
+    Buffer(VertexBuffer),
+    Vertices(Vec<Vertex>),
+}
+
+impl Tile {
+    /// uploads the triangles once, falling back to drawing them from memory every frame
+    /// when vertex buffers aren't there (software GL, old drivers)
+    fn upload(vertices: Vec<Vertex>) -> Tile {
+        if !VertexBuffer::available() {
+            return Tile::Vertices(vertices);
+        }
+        let mut buffer = VertexBuffer::new(PrimitiveType::TRIANGLES, vertices.len() as u32, VertexBufferUsage::STATIC);
+        if buffer.update(&vertices, 0) {
+            Tile::Buffer(buffer)
+        }
+        else {
+            Tile::Vertices(vertices)
+        }
+    }
+
+    pub fn draw(&self, window: &mut dyn RenderTarget) {
+        match self {
+            Tile::Buffer(buffer) => window.draw_vertex_buffer(buffer, &RenderStates::default()),
+            Tile::Vertices(vertices) => window.draw_primitives(vertices, PrimitiveType::TRIANGLES, &RenderStates::default()),
+        }
+    }
+}
+
+/// A triangle list cut into tiles, with the tiles in an R-tree so drawing only
+/// submits the triangles near the view instead of the whole layer.
+#[derive(Debug)]
+pub struct TiledVertices {
+    tiles: Vec<Tile>,
+    tree: RTree<GeomWithData<Rectangle<[f32; 2]>, usize>>,
+    pub extent: LayerExtent,
+}
+
+impl TiledVertices {
+    /// tiles drawn straight from memory, for geometry that keeps changing like tessellated lines
+    pub fn new(vertices: &[Vertex]) -> TiledVertices {
+        TiledVertices::build(vertices, Tile::Vertices)
+    }
+
+    /// tiles uploaded to vertex buffers once, for chart geometry that never changes after loading
+    pub fn new_static(vertices: &[Vertex]) -> TiledVertices {
+        let tiled = TiledVertices::build(vertices, Tile::upload);
+        let buffers = tiled.tiles.iter().filter(|x| matches!(x, Tile::Buffer(_))).count();
+        debug!("Uploaded {buffers} of {} tiles to vertex buffers", tiled.tiles.len());
+        tiled
+    }
+
+    /// `vertices` is a TRIANGLES list, each triangle goes in the tile its middle falls in
+    fn build(vertices: &[Vertex], make_tile: fn(Vec<Vertex>) -> Tile) -> TiledVertices {
+        let extent = get_vertices_extent(vertices);
+        let tile_size = (extent.MaxX - extent.MinX).max(extent.MaxY - extent.MinY) / TILES_ACROSS;
+        let mut buckets: HashMap<(i32, i32), Vec<Vertex>> = HashMap::new();
+        for triangle in vertices.chunks_exact(3) {
+            let middle = (triangle[0].position + triangle[1].position + triangle[2].position) / 3.0;
+            let cell = if tile_size > 0.0 {
+                (((middle.x - extent.MinX) / tile_size) as i32, ((middle.y - extent.MinY) / tile_size) as i32)
+            }
+            else {
+                (0, 0)
+            };
+            buckets.entry(cell).or_default().extend_from_slice(triangle);
+        }
+        let mut tiles = Vec::new();
+        let mut boxes = Vec::new();
+        for (_, tile) in buckets {
+            // triangles poke out of their cell, so the tile's box comes from what's actually in it
+            let tile_extent = get_vertices_extent(&tile);
+            let rect = Rectangle::from_corners([tile_extent.MinX, tile_extent.MinY], [tile_extent.MaxX, tile_extent.MaxY]);
+            boxes.push(GeomWithData::new(rect, tiles.len()));
+            tiles.push(make_tile(tile));
+        }
+        TiledVertices { tiles, tree: RTree::bulk_load(boxes), extent }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// the tiles touching an extent
+    pub fn tiles_in(&self, extent: &LayerExtent) -> impl Iterator<Item = &Tile> {
+        let envelope = AABB::from_corners([extent.MinX, extent.MinY], [extent.MaxX, extent.MaxY]);
+        self.tree.locate_in_envelope_intersecting(&envelope).map(|x| &self.tiles[x.data])
+    }
+
+    pub fn draw(&self, window: &mut dyn RenderTarget, view: &View) {
+        for tile in self.tiles_in(&get_view_extent(view)) {
+            tile.draw(window);
+        }
+    }
+}
@@ -1,14 +1,291 @@
 use std::f64::consts::PI;
 
-pub fn mercator_transform(coord: (f64, f64), scale: (u32, u32)) -> (f64, f64)
-{
-    let (lon, lat ) = coord;
-    //debug!("Transforming lon: {}, lat: {}", lon, lat);
-    let (width, height) = scale;
-
-    let transformed_lon = (width as f64  * (lon + 180.0) / 360.0) % (width as f64 + width as f64 / 2.0);
-    let lat_rad = lat * PI / 180.0;
-    let merc_n: f64 = ((PI / 4.0) + (lat_rad / 2.0)).tan().ln();
-    let transformed_lat = (height as f64 / 2.0) - (width as f64 * merc_n / (2.0 * PI));
-    (transformed_lon, transformed_lat)
+/// WGS84 semi-major axis in meters
+pub const WGS84_A: f64 = 6378137.0;
+/// WGS84 flattening
+pub const WGS84_F: f64 = 1.0 / 298.257223563;
+/// mean earth radius in meters, used by the spherical mercator
+pub const EARTH_MEAN_RADIUS: f64 = 6371008.8;
+/// web mercator stops here so the world is square
+pub const WEB_MERCATOR_MAX_LAT: f64 = 85.05112877980659;
+
+pub const PROJECTION_NAMES: [&str; 3] = ["mercator", "wgs84_mercator", "web_mercator"];
+
+/// Converts between lon/lat in degrees and projected x/y.
+pub trait Projection {
+    fn forward(&self, coord: (f64, f64)) -> (f64, f64);
+    fn inverse(&self, point: (f64, f64)) -> (f64, f64);
+}
+
+/// Mercator on a sphere, x/y in meters.
+pub struct SphericalMercator {
+    pub radius: f64,
+}
+
+impl Default for SphericalMercator {
+    fn default() -> SphericalMercator {
+        SphericalMercator { radius: EARTH_MEAN_RADIUS }
+    }
+}
+
+impl Projection for SphericalMercator {
+    fn forward(&self, coord: (f64, f64)) -> (f64, f64) {
+        let (lon, lat) = coord;
+        let lat_rad = lat.to_radians();
+        let merc_n: f64 = ((PI / 4.0) + (lat_rad / 2.0)).tan().ln();
+        (self.radius * lon.to_radians(), self.radius * merc_n)
+    }
+
+    fn inverse(&self, point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = point;
+        let lat = 2.0 * (y / self.radius).exp().atan() - PI / 2.0;
+        ((x / self.radius).to_degrees(), lat.to_degrees())
+    }
+}
+
+/// Mercator on the WGS84 ellipsoid, x/y in meters. This is what paper charts use.
+pub struct EllipsoidalMercator {
+    pub semi_major: f64,
+    pub eccentricity: f64,
+}
+
+impl Default for EllipsoidalMercator {
+    fn default() -> EllipsoidalMercator {
+        EllipsoidalMercator { semi_major: WGS84_A, eccentricity: (WGS84_F * (2.0 - WGS84_F)).sqrt() }
+    }
+}
+
+impl Projection for EllipsoidalMercator {
+    fn forward(&self, coord: (f64, f64)) -> (f64, f64) {
+        let (lon, lat) = coord;
+        let e = self.eccentricity;
+        let phi = lat.to_radians();
+        let e_sin = e * phi.sin();
+        let y = ((PI / 4.0 + phi / 2.0).tan() * ((1.0 - e_sin) / (1.0 + e_sin)).powf(e / 2.0)).ln();
+        (self.semi_major * lon.to_radians(), self.semi_major * y)
+    }
+
+    fn inverse(&self, point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = point;
+        let e = self.eccentricity;
+        let t = (-y / self.semi_major).exp();
+        // no closed form for latitude, iterate until it settles
+        let mut phi = PI / 2.0 - 2.0 * t.atan();
+        for _ in 0..15 {
+            let e_sin = e * phi.sin();
+            let next = PI / 2.0 - 2.0 * (t * ((1.0 - e_sin) / (1.0 + e_sin)).powf(e / 2.0)).atan();
+            if (next - phi).abs() < 1e-12 {
+                phi = next;
+                break;
+            }
+            phi = next;
+        }
+        ((x / self.semi_major).to_degrees(), phi.to_degrees())
+    }
+}
+
+/// EPSG:3857, spherical math on the WGS84 semi-major axis with latitude clamped so the world is square
+pub struct WebMercator {
+    sphere: SphericalMercator,
+}
+
+impl Default for WebMercator {
+    fn default() -> WebMercator {
+        WebMercator { sphere: SphericalMercator { radius: WGS84_A } }
+    }
+}
+
+impl Projection for WebMercator {
+    fn forward(&self, coord: (f64, f64)) -> (f64, f64) {
+        let lat = coord.1.clamp(-WEB_MERCATOR_MAX_LAT, WEB_MERCATOR_MAX_LAT);
+        self.sphere.forward((coord.0, lat))
+    }
+
+    fn inverse(&self, point: (f64, f64)) -> (f64, f64) {
+        self.sphere.inverse(point)
+    }
+}
+
+/// the same longitude moved by whole turns to within 180° of `center`
+pub fn wrap_longitude(lon: f64, center: f64) -> f64 {
+    center + (lon - center + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// the middle of the shortest stretch of longitude covering every (west, east) range, so charts either side
+/// of the antimeridian are centred on 180° rather than on 0°
+pub fn longitude_center(ranges: &[(f64, f64)]) -> Option<f64> {
+    let mut ranges: Vec<(f64, f64)> = ranges.iter().map(|(west, east)| (wrap_longitude(*west, 0.0), wrap_longitude(*west, 0.0) + (east - west))).collect();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut covered: Vec<(f64, f64)> = Vec::new();
+    for (west, east) in ranges {
+        match covered.last_mut() {
+            Some(last) if west <= last.1 => last.1 = last.1.max(east),
+            _ => covered.push((west, east)),
+        }
+    }
+    // the charts are centred opposite the widest gap between them, going round past 180° counts too
+    let first = *covered.first()?;
+    let last = *covered.last()?;
+    let mut gap = (first.0 + 360.0 - last.1, first.0);
+    for pair in covered.windows(2) {
+        if pair[1].0 - pair[0].1 > gap.0 {
+            gap = (pair[1].0 - pair[0].1, pair[1].0);
+        }
+    }
+    let (width, east_of_gap) = gap;
+    Some(wrap_longitude(east_of_gap + (360.0 - width.max(0.0)) / 2.0, 0.0))
+}
+
+/// World coordinates the charts are stored in: projected meters measured from an origin near the
+/// charts with y flipped so north is up on screen. They don't depend on the window at all, and
+/// keeping the numbers small keeps f32 vertex positions precise down to harbour scale.
+pub struct WorldProjection {
+    pub projection: Box<dyn Projection>,
+    // projected origin in meters
+    pub origin: (f64, f64),
+    // longitudes are wrapped to within 180° of this, so charts across the antimeridian stay next to each other
+    pub center_lon: f64,
+}
+
+impl WorldProjection {
+    pub fn new(projection: Box<dyn Projection>, origin: (f64, f64)) -> WorldProjection {
+        let center_lon = origin.0;
+        let origin = projection.forward(origin);
+        WorldProjection { projection, origin, center_lon }
+    }
+}
+
+impl Projection for WorldProjection {
+    fn forward(&self, coord: (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.projection.forward((wrap_longitude(coord.0, self.center_lon), coord.1));
+        (x - self.origin.0, self.origin.1 - y)
+    }
+
+    fn inverse(&self, point: (f64, f64)) -> (f64, f64) {
+        let (lon, lat) = self.projection.inverse((point.0 + self.origin.0, self.origin.1 - point.1));
+        (wrap_longitude(lon, 0.0), lat)
+    }
+}
+
+pub fn projection_from_name(name: &str) -> Option<Box<dyn Projection>> {
+    match name.to_lowercase().as_str() {
+        "mercator" => Some(Box::new(SphericalMercator::default())),
+        "wgs84_mercator" => Some(Box::new(EllipsoidalMercator::default())),
+        "web_mercator" => Some(Box::new(WebMercator::default())),
+        _ => None,
+    }
+}
+
+/// degrees and decimal minutes the way charts and GPS units write them, e.g. 37°48.074'N 122°27.007'W
+pub fn format_lat_lon(lat: f64, lon: f64) -> String {
+    let format = |value: f64, positive: char, negative: char| {
+        let hemisphere = if value < 0.0 { negative } else { positive };
+        let value = value.abs();
+        let mut degrees = value.trunc();
+        let mut minutes = (value - degrees) * 60.0;
+        // don't print 60.000' when rounding carries over
+        if minutes >= 59.9995 {
+            degrees += 1.0;
+            minutes = 0.0;
+        }
+        format!("{degrees:.0}°{minutes:06.3}'{hemisphere}")
+    };
+    format!("{} {}", format(lat, 'N', 'S'), format(lon, 'E', 'W'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATITUDES: [f64; 7] = [-80.0, -45.0, -12.5, 0.0, 23.4, 60.0, 84.0];
+
+    fn assert_round_trip(projection: &dyn Projection, coord: (f64, f64)) {
+        let (lon, lat) = projection.inverse(projection.forward(coord));
+        assert!((lon - coord.0).abs() < 1e-9 && (lat - coord.1).abs() < 1e-9, "{coord:?} came back as {:?}", (lon, lat));
+    }
+
+    #[test]
+    fn round_trips() {
+        for name in PROJECTION_NAMES {
+            let projection = projection_from_name(name).unwrap();
+            for lat in LATITUDES {
+                for lon in [-179.5, -122.45, 0.0, 151.2] {
+                    assert_round_trip(projection.as_ref(), (lon, lat));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn known_values() {
+        // EPSG:3395 and EPSG:3857 of 45°N 45°E
+        let (x, y) = EllipsoidalMercator::default().forward((45.0, 45.0));
+        assert!((x - 5009377.086).abs() < 1e-3, "{x}");
+        assert!((y - 5591295.919).abs() < 1e-3, "{y}");
+        let (x, y) = WebMercator::default().forward((45.0, 45.0));
+        assert!((x - 5009377.086).abs() < 1e-3, "{x}");
+        assert!((y - 5621521.486).abs() < 1e-3, "{y}");
+        // a degree of longitude on the mean radius sphere
+        let (x, y) = SphericalMercator::default().forward((1.0, 0.0));
+        assert!((x - 111195.080).abs() < 1e-3 && y.abs() < 1e-6, "{x} {y}");
+    }
+
+    #[test]
+    fn web_mercator_is_square() {
+        let projection = WebMercator::default();
+        let (x, _) = projection.forward((180.0, 0.0));
+        let (_, y) = projection.forward((0.0, 89.9));
+        assert!((x - y).abs() < 1e-6, "{x} {y}");
+    }
+
+    #[test]
+    fn world_coordinates() {
+        let world = WorldProjection::new(Box::new(EllipsoidalMercator::default()), (-122.0, 37.0));
+        assert_eq!(world.forward((-122.0, 37.0)), (0.0, 0.0));
+        // north is up on screen, so further north is a smaller y
+        let (x, y) = world.forward((-121.9, 37.1));
+        assert!(x > 0.0 && y < 0.0, "{x} {y}");
+        for lat in [36.5, 37.0, 37.3] {
+            assert_round_trip(&world, (-122.2, lat));
+        }
+    }
+
+    #[test]
+    fn across_the_antimeridian() {
+        let world = WorldProjection::new(Box::new(EllipsoidalMercator::default()), (180.0, -17.0));
+        let (east, _) = world.forward((179.9, -17.0));
+        let (west, _) = world.forward((-179.9, -17.0));
+        // 0.2° apart, not most of the way round the world
+        assert!(east < 0.0 && west > 0.0 && west - east < 25000.0, "{east} {west}");
+        for lon in [179.9, -179.9, -179.0] {
+            assert_round_trip(&world, (lon, -17.0));
+        }
+    }
+
+    #[test]
+    fn longitude_centers() {
+        assert_eq!(longitude_center(&[]), None);
+        assert_eq!(longitude_center(&[(-122.5, -122.0), (-122.2, -121.5)]), Some(-122.0));
+        // Fiji: cells either side of 180°
+        assert_eq!(longitude_center(&[(178.0, 179.5), (-179.5, -178.0)]), Some(-180.0));
+        assert_eq!(longitude_center(&[(170.0, 180.0), (-180.0, -150.0)]), Some(-170.0));
+        assert_eq!(wrap_longitude(-179.0, 180.0), 181.0);
+        assert_eq!(wrap_longitude(181.0, 0.0), -179.0);
+    }
+
+    #[test]
+    fn names() {
+        for name in PROJECTION_NAMES {
+            assert!(projection_from_name(name).is_some(), "{name}");
+        }
+        assert!(projection_from_name("Web_Mercator").is_some());
+        assert!(projection_from_name("lambert").is_none());
+        assert!(projection_from_name("").is_none());
+    }
+
+    #[test]
+    fn lat_lon() {
+        assert_eq!(format_lat_lon(37.80123, -122.45012), "37°48.074'N 122°27.007'W");
+        assert_eq!(format_lat_lon(-33.9999999, 18.5), "34°00.000'S 18°30.000'E");
+    }
 }
@@ -0,0 +1,247 @@
+pub const FEET_PER_METER: f64 = 3.28084;
+pub const METERS_PER_NAUTICAL_MILE: f64 = 1852.0;
+pub const METERS_PER_STATUTE_MILE: f64 = 1609.344;
+pub const METERS_PER_SECOND_PER_KNOT: f64 = METERS_PER_NAUTICAL_MILE / 3600.0;
+
+pub const DEPTH_UNIT_NAMES: [&str; 3] = ["meters", "feet", "fathoms"];
+pub const DISTANCE_UNIT_NAMES: [&str; 3] = ["nm", "km", "mi"];
+pub const SPEED_UNIT_NAMES: [&str; 3] = ["kn", "m/s", "km/h"];
+
+// S-52 shows tenths of a meter on soundings shallower than this
+const DECIMAL_SOUNDING_LIMIT: f64 = 31.0;
+// and old fathom charts show the feet on soundings shallower than this many fathoms
+const FATHOMS_FEET_LIMIT: f64 = 11.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthUnit {
+    Meters,
+    Feet,
+    // fathoms with the odd feet, like US fathom charts
+    FathomsFeet,
+}
+
+impl DepthUnit {
+    pub fn from_name(name: &str) -> Option<DepthUnit> {
+        match name.to_lowercase().as_str() {
+            "meters" | "m" => Some(DepthUnit::Meters),
+            "feet" | "ft" => Some(DepthUnit::Feet),
+            "fathoms" | "fathoms_feet" => Some(DepthUnit::FathomsFeet),
+            _ => None,
+        }
+    }
+
+    /// a depth for reading, e.g. 4.2 m, 14 ft, 2 fm 2 ft
+    pub fn format(&self, meters: f64) -> String {
+        match self {
+            DepthUnit::Meters => format!("{meters:.1} m"),
+            DepthUnit::Feet => format!("{:.0} ft", meters * FEET_PER_METER),
+            // fathom charts give drying heights in feet
+            DepthUnit::FathomsFeet if meters < 0.0 => DepthUnit::Feet.format(meters),
+            DepthUnit::FathomsFeet => {
+                let (fathoms, feet) = fathoms_feet(meters);
+                if feet == 0 { format!("{fathoms} fm") } else { format!("{fathoms} fm {feet} ft") }
+            }
+        }
+    }
+
+    /// a sounding as chart figures: the whole part and the smaller figure written beside it,
+    /// decimetres for meters and feet for fathoms
+    pub fn sounding_figures(&self, meters: f64) -> (String, Option<String>) {
+        match self {
+            DepthUnit::Meters => {
+                let tenths = (meters.abs() * 10.0) as i64;
+                let (whole, tenth) = (tenths / 10, tenths % 10);
+                let sign = if meters < 0.0 && tenths > 0 { "-" } else { "" };
+                if meters.abs() < DECIMAL_SOUNDING_LIMIT && tenth != 0 {
+                    (format!("{sign}{whole}"), Some(tenth.to_string()))
+                }
+                else {
+                    (format!("{sign}{whole}"), None)
+                }
+            }
+            DepthUnit::Feet => (format!("{:.0}", (meters * FEET_PER_METER).floor()), None),
+            DepthUnit::FathomsFeet if meters < 0.0 => DepthUnit::Feet.sounding_figures(meters),
+            DepthUnit::FathomsFeet => {
+                let (fathoms, feet) = fathoms_feet(meters);
+                if (fathoms as f64) < FATHOMS_FEET_LIMIT && feet != 0 {
+                    (fathoms.to_string(), Some(feet.to_string()))
+                }
+                else {
+                    (fathoms.to_string(), None)
+                }
+            }
+        }
+    }
+}
+
+/// whole fathoms and the left over feet, rounded down like a chart does so we never show it deeper than it is
+fn fathoms_feet(meters: f64) -> (i64, i64) {
+    let feet = (meters * FEET_PER_METER).floor() as i64;
+    (feet / 6, feet % 6)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistanceUnit {
+    NauticalMiles,
+    Kilometers,
+    StatuteMiles,
+}
+
+impl DistanceUnit {
+    pub fn from_name(name: &str) -> Option<DistanceUnit> {
+        match name.to_lowercase().as_str() {
+            "nm" | "nautical_miles" => Some(DistanceUnit::NauticalMiles),
+            "km" | "kilometers" => Some(DistanceUnit::Kilometers),
+            "mi" | "miles" | "statute_miles" => Some(DistanceUnit::StatuteMiles),
+            _ => None,
+        }
+    }
+
+    pub fn meters_per_unit(&self) -> f64 {
+        match self {
+            DistanceUnit::NauticalMiles => METERS_PER_NAUTICAL_MILE,
+            DistanceUnit::Kilometers => 1000.0,
+            DistanceUnit::StatuteMiles => METERS_PER_STATUTE_MILE,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            DistanceUnit::NauticalMiles => "nm",
+            DistanceUnit::Kilometers => "km",
+            DistanceUnit::StatuteMiles => "mi",
+        }
+    }
+
+    pub fn format(&self, meters: f64) -> String {
+        let value = meters / self.meters_per_unit();
+        if value < 10.0 {
+            format!("{value:.2} {}", self.symbol())
+        }
+        else {
+            format!("{value:.1} {}", self.symbol())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeedUnit {
+    Knots,
+    MetersPerSecond,
+    KilometersPerHour,
+}
+
+impl SpeedUnit {
+    pub fn from_name(name: &str) -> Option<SpeedUnit> {
+        match name.to_lowercase().as_str() {
+            "kn" | "knots" => Some(SpeedUnit::Knots),
+            "m/s" => Some(SpeedUnit::MetersPerSecond),
+            "km/h" => Some(SpeedUnit::KilometersPerHour),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, knots: f64) -> String {
+        match self {
+            SpeedUnit::Knots => format!("{knots:.1} kn"),
+            SpeedUnit::MetersPerSecond => format!("{:.1} m/s", knots * METERS_PER_SECOND_PER_KNOT),
+            SpeedUnit::KilometersPerHour => format!("{:.1} km/h", knots * METERS_PER_NAUTICAL_MILE / 1000.0),
+        }
+    }
+}
+
+/// The units everything on screen is shown in. Depths and distances are kept in S-57's meters
+/// and speeds in knots like NMEA sends them, they're only converted when they're shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Units {
+    pub depth: DepthUnit,
+    pub distance: DistanceUnit,
+    pub speed: SpeedUnit,
+}
+
+impl Default for Units {
+    fn default() -> Units {
+        Units { depth: DepthUnit::Feet, distance: DistanceUnit::NauticalMiles, speed: SpeedUnit::Knots }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn figures(unit: DepthUnit, meters: f64) -> (String, Option<String>) {
+        unit.sounding_figures(meters)
+    }
+
+    fn pair(whole: &str, small: Option<&str>) -> (String, Option<String>) {
+        (whole.to_string(), small.map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn names() {
+        for (i, name) in DEPTH_UNIT_NAMES.iter().enumerate() {
+            assert_eq!(DepthUnit::from_name(name), Some([DepthUnit::Meters, DepthUnit::Feet, DepthUnit::FathomsFeet][i]));
+        }
+        for (i, name) in DISTANCE_UNIT_NAMES.iter().enumerate() {
+            assert_eq!(DistanceUnit::from_name(name), Some([DistanceUnit::NauticalMiles, DistanceUnit::Kilometers, DistanceUnit::StatuteMiles][i]));
+        }
+        for (i, name) in SPEED_UNIT_NAMES.iter().enumerate() {
+            assert_eq!(SpeedUnit::from_name(name), Some([SpeedUnit::Knots, SpeedUnit::MetersPerSecond, SpeedUnit::KilometersPerHour][i]));
+        }
+        assert_eq!(DepthUnit::from_name("FT"), Some(DepthUnit::Feet));
+        assert_eq!(DepthUnit::from_name("cubits"), None);
+    }
+
+    #[test]
+    fn depths() {
+        assert_eq!(DepthUnit::Meters.format(4.2), "4.2 m");
+        // 9.95 is a hair under in binary, so it doesn't round up to 10.0
+        assert_eq!(DepthUnit::Meters.format(9.95), "9.9 m");
+        assert_eq!(DepthUnit::Feet.format(4.2), "14 ft");
+        assert_eq!(DepthUnit::FathomsFeet.format(5.0), "2 fm 4 ft");
+        assert_eq!(DepthUnit::FathomsFeet.format(12.0 / FEET_PER_METER), "2 fm");
+        // drying heights on fathom charts are in feet
+        assert_eq!(DepthUnit::FathomsFeet.format(-0.5), "-2 ft");
+    }
+
+    #[test]
+    fn meter_soundings() {
+        assert_eq!(figures(DepthUnit::Meters, 4.2), pair("4", Some("2")));
+        // tenths are cut off rather than rounded so a sounding never shows deeper than it is
+        assert_eq!(figures(DepthUnit::Meters, 9.95), pair("9", Some("9")));
+        assert_eq!(figures(DepthUnit::Meters, 9.99), pair("9", Some("9")));
+        assert_eq!(figures(DepthUnit::Meters, 10.0), pair("10", None));
+        assert_eq!(figures(DepthUnit::Meters, 0.05), pair("0", None));
+        assert_eq!(figures(DepthUnit::Meters, 30.9), pair("30", Some("9")));
+        assert_eq!(figures(DepthUnit::Meters, 31.4), pair("31", None));
+        // drying heights
+        assert_eq!(figures(DepthUnit::Meters, -1.2), pair("-1", Some("2")));
+        assert_eq!(figures(DepthUnit::Meters, -0.05), pair("0", None));
+    }
+
+    #[test]
+    fn feet_and_fathom_soundings() {
+        assert_eq!(figures(DepthUnit::Feet, 4.2), pair("13", None));
+        assert_eq!(figures(DepthUnit::FathomsFeet, 5.0), pair("2", Some("4")));
+        assert_eq!(figures(DepthUnit::FathomsFeet, 12.0 / FEET_PER_METER), pair("2", None));
+        assert_eq!(figures(DepthUnit::FathomsFeet, 20.0), pair("10", Some("5")));
+        // only whole fathoms from 11 fathoms down
+        assert_eq!(figures(DepthUnit::FathomsFeet, 21.0), pair("11", None));
+        assert_eq!(figures(DepthUnit::FathomsFeet, -0.5), pair("-2", None));
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(DistanceUnit::NauticalMiles.format(1852.0), "1.00 nm");
+        assert_eq!(DistanceUnit::NauticalMiles.format(18520.0), "10.0 nm");
+        assert_eq!(DistanceUnit::Kilometers.format(1500.0), "1.50 km");
+        assert_eq!(DistanceUnit::StatuteMiles.format(METERS_PER_STATUTE_MILE), "1.00 mi");
+    }
+
+    #[test]
+    fn speeds() {
+        assert_eq!(SpeedUnit::Knots.format(6.25), "6.2 kn");
+        assert_eq!(SpeedUnit::MetersPerSecond.format(10.0), "5.1 m/s");
+        assert_eq!(SpeedUnit::KilometersPerHour.format(10.0), "18.5 km/h");
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use sfml::graphics::View;
+use sfml::system::{Vector2f, Vector2i};
+use sfml::SfBox;
+
+use crate::geometry::DEPARE;
+use crate::line::length;
+use crate::render::get_zoom;
+
+// how far one notch of the scroll wheel zooms
+const WHEEL_ZOOM_STEP: f32 = 0.85;
+// each frame closes this much of the gap between the current and target zoom
+const ZOOM_SMOOTHING: f32 = 0.25;
+// close enough to the target to stop animating
+const ZOOM_SETTLED: f32 = 0.002;
+// a press that moves less than this many pixels is a click, not a drag
+const DRAG_THRESHOLD: i32 = 3;
+// world meters per pixel, from closer in than a berth to further out than an ocean
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 50_000.0;
+
+/// The window's view of the chart and everything that moves it: keys, dragging, the scroll wheel,
+/// touch and resizing. Zooming eases toward a target so it reads as a smooth zoom rather than jumps,
+/// except for pinching where the fingers already move smoothly.
+pub struct Viewport {
+    pub view: SfBox<View>,
+    // world units per screen pixel, the `zoom` passed to rendering
+    pub zoom: f32,
+    target_zoom: f32,
+    // screen pixel that stays over the same spot on the chart while zooming
+    zoom_anchor: Vector2f,
+    // window size in pixels
+    size: Vector2f,
+    drag_start: Option<Vector2i>,
+    drag_last: Vector2i,
+    dragged: bool,
+    // the first two fingers on the screen and where they were last, in pixels
+    touches: Vec<(u32, Vector2f)>,
+}
+
+impl Viewport {
+    /// starts out showing every chart
+    pub fn new(map: &HashMap<u16, Vec<DEPARE>>, size: (u32, u32)) -> Viewport {
+        let size = Vector2f::new(size.0 as f32, size.1 as f32);
+        let mut viewport = Viewport {
+            view: View::new(Vector2f::default(), size),
+            zoom: 1.0,
+            target_zoom: 1.0,
+            zoom_anchor: size / 2.0,
+            size,
+            drag_start: None,
+            drag_last: Vector2i::default(),
+            dragged: false,
+            touches: Vec::new(),
+        };
+        viewport.zoom_to_fit(map);
+        viewport
+    }
+
+    pub fn zoom_to_fit(&mut self, map: &HashMap<u16, Vec<DEPARE>>) {
+        // world coordinates are meters so start out one meter to the pixel and zoom to fit from there
+        let mut view = View::new(Vector2f::default(), self.size);
+        let (center, zoom_scalar) = get_zoom(map, &view);
+        if !zoom_scalar.is_finite() || zoom_scalar <= 0.0 {
+            return;
+        }
+        view.set_center(center);
+        // same limits as wheel and pinch zoom, a tiny chart would otherwise start zoomed in past them
+        self.zoom = (1.0 / zoom_scalar).clamp(MIN_ZOOM, MAX_ZOOM);
+        view.zoom(self.zoom);
+        self.target_zoom = self.zoom;
+        self.view = view;
+    }
+
+    /// moves the chart by a number of screen pixels, positive moves the view right and down
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.view.move_((dx * self.zoom, dy * self.zoom));
+    }
+
+    /// moves by a fraction of the window, for the arrow keys
+    pub fn pan_fraction(&mut self, fx: f32, fy: f32) {
+        self.pan(fx * self.size.x, fy * self.size.y);
+    }
+
+    /// eases the zoom by `factor` keeping the chart under `anchor` (in pixels) in place, less than 1 zooms in
+    pub fn zoom_toward(&mut self, factor: f32, anchor: Vector2f) {
+        self.target_zoom = (self.target_zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_anchor = anchor;
+    }
+
+    pub fn zoom_at_center(&mut self, factor: f32) {
+        self.zoom_toward(factor, self.size / 2.0);
+    }
+
+    /// one scroll wheel event at a pixel, positive delta zooms in
+    pub fn scroll(&mut self, delta: f32, x: i32, y: i32) {
+        self.zoom_toward(WHEEL_ZOOM_STEP.powf(delta), Vector2f::new(x as f32, y as f32));
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        // the world point under the anchor has to stay under it
+        let offset = self.zoom_anchor - self.size / 2.0;
+        let anchor_world = self.view.center() + offset * self.zoom;
+        self.zoom = zoom;
+        self.view.set_size(self.size * zoom);
+        self.view.set_center(anchor_world - offset * zoom);
+    }
+
+    /// steps the zoom animation, returns true while there's still more to go
+    pub fn animate(&mut self) -> bool {
+        let ratio = self.target_zoom / self.zoom;
+        if (ratio - 1.0).abs() < ZOOM_SETTLED {
+            if ratio != 1.0 {
+                self.set_zoom(self.target_zoom);
+            }
+            return false;
+        }
+        // step in log space so zooming in and out feel the same
+        self.set_zoom(self.zoom * ratio.powf(ZOOM_SMOOTHING));
+        true
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.target_zoom != self.zoom
+    }
+
+    /// keep the same scale and just show more or less of the chart. Minimising resizes to nothing,
+    /// that's ignored so the view never ends up with no size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.size = Vector2f::new(width as f32, height as f32);
+        self.view.set_size(self.size * self.zoom);
+        self.zoom_anchor = self.size / 2.0;
+    }
+
+    pub fn start_drag(&mut self, x: i32, y: i32) {
+        self.drag_start = Some(Vector2i::new(x, y));
+        self.drag_last = Vector2i::new(x, y);
+        self.dragged = false;
+    }
+
+    pub fn drag_to(&mut self, x: i32, y: i32) {
+        let start = match self.drag_start {
+            Some(start) => start,
+            None => return,
+        };
+        if !self.dragged && (x - start.x).abs().max((y - start.y).abs()) < DRAG_THRESHOLD {
+            return;
+        }
+        self.dragged = true;
+        // the chart follows the mouse, so the view goes the other way
+        self.pan((self.drag_last.x - x) as f32, (self.drag_last.y - y) as f32);
+        self.drag_last = Vector2i::new(x, y);
+    }
+
+    /// returns true when the button came up without the mouse really moving, i.e. it was a click
+    pub fn end_drag(&mut self) -> bool {
+        let clicked = self.drag_start.is_some() && !self.dragged;
+        self.drag_start = None;
+        clicked
+    }
+
+    pub fn touch_began(&mut self, finger: u32, x: i32, y: i32) {
+        if self.touches.len() < 2 {
+            self.touches.push((finger, Vector2f::new(x as f32, y as f32)));
+        }
+    }
+
+    /// one finger pans, two pinch to zoom around the middle of them and pan as that moves
+    pub fn touch_moved(&mut self, finger: u32, x: i32, y: i32) {
+        let before: Vec<Vector2f> = self.touches.iter().map(|x| x.1).collect();
+        match self.touches.iter_mut().find(|x| x.0 == finger) {
+            Some(touch) => touch.1 = Vector2f::new(x as f32, y as f32),
+            None => return,
+        }
+        let after: Vec<Vector2f> = self.touches.iter().map(|x| x.1).collect();
+        if let ([a0, b0], [a1, b1]) = (&before[..], &after[..]) {
+            let (middle_before, middle_after) = ((*a0 + *b0) / 2.0, (*a1 + *b1) / 2.0);
+            self.pan(middle_before.x - middle_after.x, middle_before.y - middle_after.y);
+            let (spread_before, spread_after) = (length(*b0 - *a0), length(*b1 - *a1));
+            if spread_before > 0.0 && spread_after > 0.0 {
+                self.zoom_anchor = middle_after;
+                self.target_zoom = (self.zoom * spread_before / spread_after).clamp(MIN_ZOOM, MAX_ZOOM);
+                self.set_zoom(self.target_zoom);
+            }
+        }
+        else if let ([before], [after]) = (&before[..], &after[..]) {
+            self.pan(before.x - after.x, before.y - after.y);
+        }
+    }
+
+    pub fn touch_ended(&mut self, finger: u32) {
+        self.touches.retain(|x| x.0 != finger);
+    }
+}